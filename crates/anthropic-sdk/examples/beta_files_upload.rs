@@ -1,6 +1,5 @@
 use anthropic_sdk::resources::beta::files::FileUploadParams;
 use anthropic_sdk::{Anthropic, ClientOptions};
-use std::path::PathBuf;
 
 fn usage() -> ! {
     eprintln!("usage: cargo run -p anthropic-sdk-rs --example beta_files_upload -- <path> [mime_type] [filename]");
@@ -14,20 +13,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mime_type = args.next();
     let filename = args.next();
 
+    let mut params = FileUploadParams::path(path);
+    if let Some(mime_type) = mime_type {
+        params = params.mime_type(mime_type);
+    }
+    if let Some(filename) = filename {
+        params = params.filename(filename);
+    }
+
     let client = Anthropic::new(ClientOptions::default())?;
-    let file = client
-        .beta
-        .files
-        .upload(
-            FileUploadParams {
-                path: PathBuf::from(path),
-                filename,
-                mime_type,
-                betas: None,
-            },
-            None,
-        )
-        .await?;
+    let file = client.beta.files.upload(params, None).await?;
 
     println!("uploaded file id={}", file.id);
     Ok(())
@@ -1,6 +1,5 @@
 use anthropic_sdk::types::models::ModelInfo;
-use anthropic_sdk::{Anthropic, ClientOptions};
-use reqwest::header::HeaderMap;
+use anthropic_sdk::Anthropic;
 use serde_json::json;
 use std::time::Duration;
 use wiremock::matchers::{method, path};
@@ -25,14 +24,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .mount(&server)
         .await;
 
-    let client = Anthropic::new(ClientOptions {
-        api_key: Some("test-key".to_string()),
-        auth_token: None,
-        base_url: Some(server.uri()),
-        timeout: Some(Duration::from_millis(250)),
-        max_retries: Some(0),
-        default_headers: HeaderMap::new(),
-    })?;
+    let client = Anthropic::builder()
+        .api_key("test-key")
+        .base_url(server.uri())
+        .timeout(Duration::from_millis(250))
+        .max_retries(0)
+        .build()?;
 
     let page = client.models.list(None, None).await?;
     let models: Vec<ModelInfo> = page.data;
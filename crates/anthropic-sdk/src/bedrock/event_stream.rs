@@ -0,0 +1,115 @@
+use crate::error::Error;
+use bytes::{Buf, Bytes, BytesMut};
+use std::collections::BTreeMap;
+
+// Prelude (total length, headers length, prelude CRC) plus the trailing message CRC.
+const PRELUDE_LEN: usize = 12;
+const TRAILER_LEN: usize = 4;
+
+// One frame of the `application/vnd.amazon.eventstream` framing. Only string headers are kept;
+// they are the only kind Bedrock uses (`:message-type`, `:event-type`, ...).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct EventMessage {
+    pub headers: BTreeMap<String, String>,
+    pub payload: Bytes,
+}
+
+impl EventMessage {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct EventStreamDecoder {
+    buffer: BytesMut,
+}
+
+impl EventStreamDecoder {
+    pub fn push(&mut self, chunk: &[u8]) {
+        self.buffer.extend_from_slice(chunk);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    pub fn next_message(&mut self) -> Result<Option<EventMessage>, Error> {
+        if self.buffer.len() < PRELUDE_LEN {
+            return Ok(None);
+        }
+        let total_len = read_u32(&self.buffer[0..4]) as usize;
+        let headers_len = read_u32(&self.buffer[4..8]) as usize;
+        let prelude_crc = read_u32(&self.buffer[8..12]);
+        if crc32fast::hash(&self.buffer[0..8]) != prelude_crc {
+            return Err(invalid("prelude checksum mismatch"));
+        }
+        if total_len < PRELUDE_LEN + headers_len + TRAILER_LEN {
+            return Err(invalid("frame is shorter than its headers"));
+        }
+        if self.buffer.len() < total_len {
+            return Ok(None);
+        }
+
+        let mut frame = self.buffer.split_to(total_len).freeze();
+        let message_crc = read_u32(&frame[total_len - TRAILER_LEN..]);
+        if crc32fast::hash(&frame[..total_len - TRAILER_LEN]) != message_crc {
+            return Err(invalid("message checksum mismatch"));
+        }
+
+        frame.advance(PRELUDE_LEN);
+        let headers = parse_headers(frame.split_to(headers_len))?;
+        let payload = frame.split_to(frame.len() - TRAILER_LEN);
+        Ok(Some(EventMessage { headers, payload }))
+    }
+}
+
+fn parse_headers(mut raw: Bytes) -> Result<BTreeMap<String, String>, Error> {
+    let mut headers = BTreeMap::new();
+    while raw.has_remaining() {
+        let name_len = raw.get_u8() as usize;
+        let name = take(&mut raw, name_len)?;
+        let name = String::from_utf8_lossy(&name).into_owned();
+        if !raw.has_remaining() {
+            return Err(invalid("truncated header"));
+        }
+        let value_len = match raw.get_u8() {
+            // bool true / bool false carry no value.
+            0 | 1 => 0,
+            2 => 1,
+            3 => 2,
+            4 => 4,
+            5 | 8 => 8,
+            9 => 16,
+            6 => {
+                let len = take(&mut raw, 2)?.get_u16() as usize;
+                take(&mut raw, len)?;
+                continue;
+            }
+            7 => {
+                let len = take(&mut raw, 2)?.get_u16() as usize;
+                let value = take(&mut raw, len)?;
+                headers.insert(name, String::from_utf8_lossy(&value).into_owned());
+                continue;
+            }
+            other => return Err(invalid(&format!("unknown header type {other}"))),
+        };
+        take(&mut raw, value_len)?;
+    }
+    Ok(headers)
+}
+
+fn take(raw: &mut Bytes, len: usize) -> Result<Bytes, Error> {
+    if raw.remaining() < len {
+        return Err(invalid("truncated header"));
+    }
+    Ok(raw.split_to(len))
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn invalid(message: &str) -> Error {
+    Error::InvalidEventStream(message.to_string())
+}
@@ -0,0 +1,409 @@
+mod event_stream;
+mod sigv4;
+
+use crate::bedrock::event_stream::{EventMessage, EventStreamDecoder};
+use crate::client::{
+    BodyStream, ClientOptions, HttpBody, HttpRequest, HttpResponse, HttpTransport, Inner,
+    RetryPolicy,
+};
+use crate::error::Error;
+use crate::middleware::Middleware;
+use crate::resources::messages::Messages;
+use base64::Engine as _;
+use bytes::Bytes;
+use futures_util::future::BoxFuture;
+use futures_util::StreamExt;
+use reqwest::header::{HeaderName, HeaderValue, ACCEPT, AUTHORIZATION, CONTENT_TYPE};
+use reqwest::{Client as HttpClient, Method};
+use serde_json::{json, Value};
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+const SERVICE: &str = "bedrock";
+const BEDROCK_VERSION: &str = "bedrock-2023-05-31";
+
+const HEADER_ANTHROPIC_BETA: HeaderName = HeaderName::from_static("anthropic-beta");
+const HEADER_REQUEST_ID: HeaderName = HeaderName::from_static("request-id");
+const HEADER_X_AMZN_ERROR_TYPE: HeaderName = HeaderName::from_static("x-amzn-errortype");
+const HEADER_X_AMZN_REQUEST_ID: HeaderName = HeaderName::from_static("x-amzn-requestid");
+const HEADER_X_API_KEY: HeaderName = HeaderName::from_static("x-api-key");
+
+#[derive(Clone)]
+pub struct AwsCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+}
+
+impl fmt::Debug for AwsCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AwsCredentials")
+            .field("access_key_id", &self.access_key_id)
+            .field("secret_access_key", &"[redacted]")
+            .field(
+                "session_token",
+                &self.session_token.as_ref().map(|_| "[redacted]"),
+            )
+            .finish()
+    }
+}
+
+impl AwsCredentials {
+    pub fn new(access_key_id: impl Into<String>, secret_access_key: impl Into<String>) -> Self {
+        Self {
+            access_key_id: access_key_id.into(),
+            secret_access_key: secret_access_key.into(),
+            session_token: None,
+        }
+    }
+
+    pub fn session_token(mut self, session_token: impl Into<String>) -> Self {
+        self.session_token = Some(session_token.into());
+        self
+    }
+
+    pub fn from_env() -> Option<Self> {
+        let access_key_id = std::env::var("AWS_ACCESS_KEY_ID").ok()?;
+        let secret_access_key = std::env::var("AWS_SECRET_ACCESS_KEY").ok()?;
+        Some(Self {
+            access_key_id,
+            secret_access_key,
+            session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+        })
+    }
+}
+
+// Messages API served by Amazon Bedrock. Only `messages.create` and `messages.stream` (and the
+// helpers built on them) are available; other endpoints fail without sending a request.
+#[derive(Clone)]
+pub struct AnthropicBedrock {
+    pub messages: Messages,
+}
+
+impl AnthropicBedrock {
+    pub fn builder() -> BedrockBuilder {
+        BedrockBuilder::new()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BedrockBuilder {
+    region: Option<String>,
+    credentials: Option<AwsCredentials>,
+    options: ClientOptions,
+}
+
+impl Default for BedrockBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BedrockBuilder {
+    pub fn new() -> Self {
+        Self {
+            region: None,
+            credentials: None,
+            options: ClientOptions::empty(),
+        }
+    }
+
+    pub fn region(mut self, region: impl Into<String>) -> Self {
+        self.region = Some(region.into());
+        self
+    }
+
+    pub fn credentials(mut self, credentials: AwsCredentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.options.base_url = Some(base_url.into());
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.options.timeout = Some(timeout);
+        self
+    }
+
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.options.max_retries = Some(max_retries);
+        self
+    }
+
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.options.retry_policy = Some(retry_policy);
+        self
+    }
+
+    pub fn http_client(mut self, http_client: HttpClient) -> Self {
+        self.options.http_client = Some(http_client);
+        self
+    }
+
+    pub fn transport(mut self, transport: Arc<dyn HttpTransport>) -> Self {
+        self.options.transport = Some(transport);
+        self
+    }
+
+    pub fn middleware(mut self, middleware: Arc<dyn Middleware>) -> Self {
+        self.options.middleware.push(middleware);
+        self
+    }
+
+    // Region and credentials fall back to `AWS_REGION`/`AWS_DEFAULT_REGION` and the standard
+    // `AWS_ACCESS_KEY_ID`/`AWS_SECRET_ACCESS_KEY`/`AWS_SESSION_TOKEN` variables.
+    pub fn build(self) -> Result<AnthropicBedrock, Error> {
+        let region = self
+            .region
+            .or_else(|| std::env::var("AWS_REGION").ok())
+            .or_else(|| std::env::var("AWS_DEFAULT_REGION").ok())
+            .ok_or_else(|| {
+                Error::Internal("bedrock region is missing; set region or AWS_REGION".to_string())
+            })?;
+        let credentials = self
+            .credentials
+            .or_else(AwsCredentials::from_env)
+            .ok_or(Error::AuthMissing)?;
+
+        let mut options = self.options;
+        if options.base_url.is_none() {
+            options.base_url = Some(
+                std::env::var("ANTHROPIC_BEDROCK_BASE_URL")
+                    .unwrap_or_else(|_| format!("https://bedrock-runtime.{region}.amazonaws.com")),
+            );
+        }
+
+        let inner = Inner::with_transport_auth(options, |transport| {
+            Arc::new(BedrockTransport {
+                inner: transport,
+                region,
+                credentials,
+            })
+        })?;
+        Ok(AnthropicBedrock {
+            messages: Messages::new(Arc::new(inner)),
+        })
+    }
+}
+
+// Rewrites Messages API requests into `invoke-model` calls, signs them, and turns the
+// event-stream responses back into the server-sent events the rest of the client expects.
+struct BedrockTransport {
+    inner: Arc<dyn HttpTransport>,
+    region: String,
+    credentials: AwsCredentials,
+}
+
+impl fmt::Debug for BedrockTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BedrockTransport")
+            .field("inner", &self.inner)
+            .field("region", &self.region)
+            .finish_non_exhaustive()
+    }
+}
+
+impl HttpTransport for BedrockTransport {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, Error>> {
+        Box::pin(async move {
+            let (request, stream) = self.prepare(request)?;
+            let mut response = self.inner.send(request).await?;
+            if let Some(id) = response.headers.get(HEADER_X_AMZN_REQUEST_ID).cloned() {
+                response.headers.entry(HEADER_REQUEST_ID).or_insert(id);
+            }
+
+            if !response.status.is_success() {
+                let status = response.status;
+                let headers = response.headers.clone();
+                let exception = headers
+                    .get(HEADER_X_AMZN_ERROR_TYPE)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.split(':').next())
+                    .map(str::to_string);
+                let bytes = response.bytes().await?;
+                let message = serde_json::from_slice::<Value>(&bytes)
+                    .ok()
+                    .and_then(|v| v.get("message")?.as_str().map(str::to_string))
+                    .unwrap_or_else(|| String::from_utf8_lossy(&bytes).into_owned());
+                let body = error_body(exception.as_deref(), &message);
+                return Ok(HttpResponse::new(status, headers, body.to_string()));
+            }
+
+            if stream {
+                response
+                    .headers
+                    .insert(CONTENT_TYPE, HeaderValue::from_static("text/event-stream"));
+                response.body = event_stream_to_sse(response.body);
+            }
+            Ok(response)
+        })
+    }
+}
+
+impl BedrockTransport {
+    fn prepare(&self, mut request: HttpRequest) -> Result<(HttpRequest, bool), Error> {
+        let path = request.url.path().to_string();
+        let prefix = match path.strip_suffix("/v1/messages") {
+            Some(prefix) if request.method == Method::POST => prefix,
+            _ => {
+                return Err(Error::Internal(format!(
+                    "{} {path} is not supported on Bedrock",
+                    request.method
+                )))
+            }
+        };
+
+        let bytes = request
+            .body
+            .as_ref()
+            .and_then(HttpBody::as_bytes)
+            .ok_or_else(|| Error::Internal("bedrock requests need a JSON body".to_string()))?;
+        let mut body = serde_json::from_slice::<Value>(bytes)?;
+        let object = body
+            .as_object_mut()
+            .ok_or_else(|| Error::Internal("bedrock requests need a JSON object".to_string()))?;
+        let model = match object.remove("model") {
+            Some(Value::String(model)) => model,
+            _ => return Err(Error::Internal("bedrock requests need a model".to_string())),
+        };
+        let stream = object.remove("stream") == Some(Value::Bool(true));
+        object
+            .entry("anthropic_version")
+            .or_insert_with(|| Value::from(BEDROCK_VERSION));
+        // Bedrock takes beta flags in the body rather than as a header.
+        if let Some(betas) = request.headers.remove(HEADER_ANTHROPIC_BETA) {
+            let betas: Vec<Value> = betas
+                .to_str()
+                .unwrap_or_default()
+                .split(',')
+                .map(|beta| Value::from(beta.trim()))
+                .collect();
+            object.insert("anthropic_beta".to_string(), Value::Array(betas));
+        }
+
+        let action = if stream {
+            "invoke-with-response-stream"
+        } else {
+            "invoke"
+        };
+        let new_path = format!("{prefix}/model/{}/{action}", sigv4::uri_encode(&model));
+        request.url.set_path(&new_path);
+        request.url.set_query(None);
+
+        request.headers.remove(HEADER_X_API_KEY);
+        request.headers.remove(AUTHORIZATION);
+        request
+            .headers
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        let accept = if stream {
+            "application/vnd.amazon.eventstream"
+        } else {
+            "application/json"
+        };
+        request
+            .headers
+            .insert(ACCEPT, HeaderValue::from_static(accept));
+        request.body = Some(HttpBody::Bytes(Bytes::from(serde_json::to_vec(&body)?)));
+
+        sigv4::sign(
+            &mut request,
+            &self.credentials,
+            &self.region,
+            SERVICE,
+            SystemTime::now(),
+        )?;
+        Ok((request, stream))
+    }
+}
+
+fn event_stream_to_sse(body: BodyStream) -> BodyStream {
+    futures_util::stream::unfold(
+        (body, EventStreamDecoder::default(), false),
+        |(mut body, mut decoder, done)| async move {
+            if done {
+                return None;
+            }
+            loop {
+                match decoder.next_message() {
+                    Ok(Some(message)) => match to_sse(&message) {
+                        Ok(Some(event)) => return Some((Ok(event), (body, decoder, false))),
+                        Ok(None) => continue,
+                        Err(err) => return Some((Err(err), (body, decoder, true))),
+                    },
+                    Ok(None) => {}
+                    Err(err) => return Some((Err(err), (body, decoder, true))),
+                }
+
+                match body.next().await {
+                    Some(Ok(chunk)) => decoder.push(&chunk),
+                    Some(Err(err)) => return Some((Err(err), (body, decoder, true))),
+                    None if decoder.is_empty() => return None,
+                    None => {
+                        let err = Error::InvalidEventStream("truncated frame".to_string());
+                        return Some((Err(err), (body, decoder, true)));
+                    }
+                }
+            }
+        },
+    )
+    .boxed()
+}
+
+// `chunk` events wrap one Messages API stream event as base64 JSON; exceptions become `error`
+// events so they surface exactly like errors sent by the first-party API.
+fn to_sse(message: &EventMessage) -> Result<Option<Bytes>, Error> {
+    let payload = serde_json::from_slice::<Value>(&message.payload).unwrap_or(Value::Null);
+    match message.header(":message-type") {
+        Some("exception") | Some("error") => {
+            let exception = message
+                .header(":exception-type")
+                .or_else(|| message.header(":error-code"));
+            let text = payload
+                .get("message")
+                .and_then(Value::as_str)
+                .or_else(|| message.header(":error-message"))
+                .unwrap_or("unknown error");
+            Ok(Some(sse_event("error", &error_body(exception, text))))
+        }
+        _ if message.header(":event-type") == Some("chunk") => {
+            let encoded = payload
+                .get("bytes")
+                .and_then(Value::as_str)
+                .ok_or_else(|| {
+                    Error::InvalidEventStream("chunk without a bytes field".to_string())
+                })?;
+            let decoded = base64::engine::general_purpose::STANDARD
+                .decode(encoded)
+                .map_err(|e| Error::InvalidEventStream(e.to_string()))?;
+            let event = serde_json::from_slice::<Value>(&decoded)?;
+            let name = event
+                .get("type")
+                .and_then(Value::as_str)
+                .unwrap_or("message")
+                .to_string();
+            Ok(Some(sse_event(&name, &event)))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn sse_event(name: &str, data: &Value) -> Bytes {
+    Bytes::from(format!("event: {name}\ndata: {data}\n\n"))
+}
+
+fn error_body(exception: Option<&str>, message: &str) -> Value {
+    let kind = match exception.map(str::to_ascii_lowercase).as_deref() {
+        Some("throttlingexception") => "rate_limit_error",
+        Some("validationexception") => "invalid_request_error",
+        Some("accessdeniedexception") => "permission_error",
+        Some("resourcenotfoundexception") => "not_found_error",
+        Some("serviceunavailableexception") => "overloaded_error",
+        Some("modeltimeoutexception") => "timeout_error",
+        _ => "api_error",
+    };
+    json!({"type": "error", "error": {"type": kind, "message": message}})
+}
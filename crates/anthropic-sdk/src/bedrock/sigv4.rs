@@ -0,0 +1,274 @@
+use crate::bedrock::AwsCredentials;
+use crate::client::{HttpBody, HttpRequest};
+use crate::error::Error;
+use crate::time::civil_from_days;
+use hmac::{Hmac, Mac};
+use reqwest::header::{HeaderName, HeaderValue, HOST};
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+const HEADER_X_AMZ_DATE: HeaderName = HeaderName::from_static("x-amz-date");
+const HEADER_X_AMZ_SECURITY_TOKEN: HeaderName = HeaderName::from_static("x-amz-security-token");
+
+// Signs `request` in place with AWS Signature Version 4, adding `host`, `x-amz-date`, the session
+// token if any, and `authorization`. Only `host`, `content-type` and `x-amz-*` headers are signed
+// so that headers added further down the stack cannot invalidate the signature.
+pub(crate) fn sign(
+    request: &mut HttpRequest,
+    credentials: &AwsCredentials,
+    region: &str,
+    service: &str,
+    now: SystemTime,
+) -> Result<(), Error> {
+    let (amz_date, date) = timestamp(now);
+
+    let host = match (request.url.host_str(), request.url.port()) {
+        (Some(host), Some(port)) => format!("{host}:{port}"),
+        (Some(host), None) => host.to_string(),
+        (None, _) => return Err(Error::Internal("request URL has no host".to_string())),
+    };
+    request.headers.insert(HOST, HeaderValue::from_str(&host)?);
+    request
+        .headers
+        .insert(HEADER_X_AMZ_DATE, HeaderValue::from_str(&amz_date)?);
+    match &credentials.session_token {
+        Some(token) => {
+            request
+                .headers
+                .insert(HEADER_X_AMZ_SECURITY_TOKEN, HeaderValue::from_str(token)?);
+        }
+        None => {
+            request.headers.remove(HEADER_X_AMZ_SECURITY_TOKEN);
+        }
+    }
+
+    let payload_hash = match &request.body {
+        Some(HttpBody::Bytes(bytes)) => hex_sha256(bytes),
+        Some(HttpBody::Stream(_)) => "UNSIGNED-PAYLOAD".to_string(),
+        None => hex_sha256(b""),
+    };
+
+    let mut signed: Vec<(String, String)> = request
+        .headers
+        .iter()
+        .filter(|(name, _)| {
+            let name = name.as_str();
+            name == "host" || name == "content-type" || name.starts_with("x-amz-")
+        })
+        .map(|(name, value)| {
+            let value = String::from_utf8_lossy(value.as_bytes());
+            (
+                name.as_str().to_string(),
+                value.split_whitespace().collect::<Vec<_>>().join(" "),
+            )
+        })
+        .collect();
+    signed.sort();
+    let signed_headers = signed
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_headers: String = signed
+        .iter()
+        .map(|(name, value)| format!("{name}:{value}\n"))
+        .collect();
+
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        request.method,
+        canonical_uri(request.url.path()),
+        canonical_query(&request.url),
+        canonical_headers,
+        signed_headers,
+        payload_hash,
+    );
+
+    let scope = format!("{date}/{region}/{service}/aws4_request");
+    let string_to_sign = format!(
+        "{ALGORITHM}\n{amz_date}\n{scope}\n{}",
+        hex_sha256(canonical_request.as_bytes())
+    );
+
+    let key = [date.as_str(), region, service, "aws4_request"]
+        .iter()
+        .fold(
+            format!("AWS4{}", credentials.secret_access_key).into_bytes(),
+            |key, part| hmac_sha256(&key, part.as_bytes()),
+        );
+    let signature = hex(&hmac_sha256(&key, string_to_sign.as_bytes()));
+
+    let authorization = format!(
+        "{ALGORITHM} Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+        credentials.access_key_id
+    );
+    request.headers.insert(
+        reqwest::header::AUTHORIZATION,
+        HeaderValue::from_str(&authorization)?,
+    );
+    Ok(())
+}
+
+// Every path segment is encoded again, as required for all services other than S3.
+fn canonical_uri(path: &str) -> String {
+    if path.is_empty() {
+        return "/".to_string();
+    }
+    path.split('/')
+        .map(uri_encode)
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn canonical_query(url: &reqwest::Url) -> String {
+    let mut pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(k, v)| (uri_encode(&k), uri_encode(&v)))
+        .collect();
+    pairs.sort();
+    pairs
+        .iter()
+        .map(|(k, v)| format!("{k}={v}"))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+pub(crate) fn uri_encode(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(byte as char)
+            }
+            _ => {
+                let _ = write!(out, "%{byte:02X}");
+            }
+        }
+    }
+    out
+}
+
+// Returns the `YYYYMMDDTHHMMSSZ` timestamp and its `YYYYMMDD` date.
+fn timestamp(now: SystemTime) -> (String, String) {
+    let secs = now
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let days = secs.div_euclid(86_400);
+    let rem = secs.rem_euclid(86_400);
+
+    let (year, month, day) = civil_from_days(days);
+
+    let date = format!("{year:04}{month:02}{day:02}");
+    let amz_date = format!(
+        "{date}T{:02}{:02}{:02}Z",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    );
+    (amz_date, date)
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex_sha256(data: &[u8]) -> String {
+    hex(&Sha256::digest(data))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(out, "{byte:02x}");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::Method;
+    use std::time::Duration;
+
+    // Fixtures from the AWS Signature Version 4 test suite.
+    fn suite_credentials() -> AwsCredentials {
+        AwsCredentials::new("AKIDEXAMPLE", "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY")
+    }
+
+    fn suite_time() -> SystemTime {
+        // 2015-08-30T12:36:00Z
+        UNIX_EPOCH + Duration::from_secs(1_440_938_160)
+    }
+
+    fn signed(method: Method, content_type: Option<&'static str>, body: &'static [u8]) -> String {
+        let mut request = HttpRequest {
+            method,
+            url: "https://example.amazonaws.com/".parse().unwrap(),
+            headers: Default::default(),
+            body: (!body.is_empty()).then(|| HttpBody::Bytes(body.into())),
+        };
+        if let Some(content_type) = content_type {
+            request.headers.insert(
+                reqwest::header::CONTENT_TYPE,
+                HeaderValue::from_static(content_type),
+            );
+        }
+        sign(
+            &mut request,
+            &suite_credentials(),
+            "us-east-1",
+            "service",
+            suite_time(),
+        )
+        .unwrap();
+        assert_eq!(request.headers["x-amz-date"], "20150830T123600Z");
+        request.headers[reqwest::header::AUTHORIZATION]
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn matches_the_aws_test_suite() {
+        assert_eq!(
+            signed(Method::GET, None, b""),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host;x-amz-date, \
+             Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+        assert_eq!(
+            signed(
+                Method::POST,
+                Some("application/x-www-form-urlencoded"),
+                b"Param1=value1"
+            ),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=content-type;host;x-amz-date, \
+             Signature=ff11897932ad3f4e8b18135d722051e5ac45fc38421b1da7b9d196a0fe09473a"
+        );
+    }
+
+    #[test]
+    fn model_ids_with_colons_are_encoded_twice_in_the_canonical_uri() {
+        let model = "anthropic.claude-3-5-sonnet-20241022-v2:0";
+        let url: reqwest::Url = format!(
+            "https://bedrock-runtime.us-east-1.amazonaws.com/model/{}/invoke",
+            uri_encode(model)
+        )
+        .parse()
+        .unwrap();
+        assert_eq!(
+            url.path(),
+            "/model/anthropic.claude-3-5-sonnet-20241022-v2%3A0/invoke"
+        );
+        assert_eq!(
+            canonical_uri(url.path()),
+            "/model/anthropic.claude-3-5-sonnet-20241022-v2%253A0/invoke"
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use crate::client::{
+    Anthropic, CircuitBreaker, ClientOptions, CredentialProvider, HttpConfig, HttpTransport,
+    RateLimiter, RetryPolicy,
+};
+use crate::error::Error;
+use crate::middleware::Middleware;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Certificate, Client as HttpClient, Proxy};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct ClientBuilder {
+    options: ClientOptions,
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClientBuilder {
+    pub fn new() -> Self {
+        Self {
+            options: ClientOptions::empty(),
+        }
+    }
+
+    pub fn from_env(mut self) -> Self {
+        let env = ClientOptions::default();
+        self.options.api_key = self.options.api_key.or(env.api_key);
+        self.options.auth_token = self.options.auth_token.or(env.auth_token);
+        self.options.base_url = self.options.base_url.or(env.base_url);
+        self
+    }
+
+    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.options.api_key = Some(api_key.into());
+        self
+    }
+
+    pub fn auth_token(mut self, auth_token: impl Into<String>) -> Self {
+        self.options.auth_token = Some(auth_token.into());
+        self
+    }
+
+    pub fn credential_provider(mut self, provider: Arc<dyn CredentialProvider>) -> Self {
+        self.options.credential_provider = Some(provider);
+        self
+    }
+
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.options.base_url = Some(base_url.into());
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.options.timeout = Some(timeout);
+        self
+    }
+
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.options.max_retries = Some(max_retries);
+        self
+    }
+
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.options.retry_policy = Some(retry_policy);
+        self
+    }
+
+    pub fn default_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.options.default_headers.insert(name, value);
+        self
+    }
+
+    pub fn default_headers(mut self, headers: HeaderMap) -> Self {
+        for (name, value) in headers.iter() {
+            self.options.default_headers.insert(name, value.clone());
+        }
+        self
+    }
+
+    pub fn http_client(mut self, http_client: HttpClient) -> Self {
+        self.options.http_client = Some(http_client);
+        self
+    }
+
+    pub fn http_config(mut self, config: HttpConfig) -> Self {
+        self.options.http = Some(config);
+        self
+    }
+
+    pub fn transport(mut self, transport: Arc<dyn HttpTransport>) -> Self {
+        self.options.transport = Some(transport);
+        self
+    }
+
+    pub fn middleware(mut self, middleware: Arc<dyn Middleware>) -> Self {
+        self.options.middleware.push(middleware);
+        self
+    }
+
+    pub fn rate_limiter(mut self, rate_limiter: Arc<RateLimiter>) -> Self {
+        self.options.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    pub fn circuit_breaker(mut self, circuit_breaker: Arc<CircuitBreaker>) -> Self {
+        self.options.circuit_breaker = Some(circuit_breaker);
+        self
+    }
+
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.http_mut().proxies.push(proxy);
+        self
+    }
+
+    pub fn root_certificate(mut self, certificate: Certificate) -> Self {
+        self.http_mut().root_certificates.push(certificate);
+        self
+    }
+
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.http_mut().connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    pub fn build(self) -> Result<Anthropic, Error> {
+        Anthropic::new(self.options)
+    }
+
+    fn http_mut(&mut self) -> &mut HttpConfig {
+        self.options.http.get_or_insert_with(HttpConfig::default)
+    }
+}
@@ -0,0 +1,198 @@
+use crate::client::HttpRequest;
+use crate::error::Error;
+use reqwest::StatusCode;
+use serde_json::Value;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitScope {
+    Endpoint,
+    Model,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    scope: CircuitScope,
+    failure_rate: f64,
+    minimum_requests: usize,
+    window_size: usize,
+    open_duration: Duration,
+    circuits: Mutex<BTreeMap<String, Circuit>>,
+}
+
+#[derive(Debug)]
+enum Circuit {
+    Closed { outcomes: VecDeque<bool> },
+    Open { until: Instant },
+    // Only one probe is let through at a time; `since` guards against a probe that never reports.
+    HalfOpen { since: Instant },
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CircuitBreaker {
+    pub fn new() -> Self {
+        Self {
+            scope: CircuitScope::Endpoint,
+            failure_rate: 0.5,
+            minimum_requests: 10,
+            window_size: 20,
+            open_duration: Duration::from_secs(30),
+            circuits: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn scope(mut self, scope: CircuitScope) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    pub fn failure_rate(mut self, failure_rate: f64) -> Self {
+        self.failure_rate = failure_rate;
+        self
+    }
+
+    pub fn minimum_requests(mut self, minimum_requests: usize) -> Self {
+        self.minimum_requests = minimum_requests;
+        self
+    }
+
+    pub fn window_size(mut self, window_size: usize) -> Self {
+        self.window_size = window_size.max(1);
+        self
+    }
+
+    pub fn open_duration(mut self, open_duration: Duration) -> Self {
+        self.open_duration = open_duration;
+        self
+    }
+
+    pub fn state(&self, key: &str) -> CircuitState {
+        let circuits = self.circuits.lock().unwrap();
+        circuits
+            .get(key)
+            .map(|c| c.state(Instant::now()))
+            .unwrap_or(CircuitState::Closed)
+    }
+
+    pub fn states(&self) -> BTreeMap<String, CircuitState> {
+        let now = Instant::now();
+        let circuits = self.circuits.lock().unwrap();
+        circuits
+            .iter()
+            .map(|(key, circuit)| (key.clone(), circuit.state(now)))
+            .collect()
+    }
+
+    pub fn reset(&self) {
+        self.circuits.lock().unwrap().clear();
+    }
+
+    pub(crate) fn key(&self, request: &HttpRequest) -> String {
+        let model = match self.scope {
+            CircuitScope::Endpoint => None,
+            CircuitScope::Model => request
+                .body
+                .as_ref()
+                .and_then(|body| body.as_bytes())
+                .and_then(|bytes| serde_json::from_slice::<Value>(bytes).ok())
+                .and_then(|body| body.get("model")?.as_str().map(str::to_string)),
+        };
+        model.unwrap_or_else(|| format!("{} {}", request.method, request.url.path()))
+    }
+
+    pub(crate) fn acquire(&self, key: &str) -> Result<(), Error> {
+        let now = Instant::now();
+        let mut circuits = self.circuits.lock().unwrap();
+        let circuit = circuits.entry(key.to_string()).or_insert(Circuit::Closed {
+            outcomes: VecDeque::new(),
+        });
+
+        match circuit {
+            Circuit::Closed { .. } => Ok(()),
+            Circuit::Open { until } if *until <= now => {
+                *circuit = Circuit::HalfOpen { since: now };
+                Ok(())
+            }
+            Circuit::HalfOpen { since } if *since + self.open_duration <= now => {
+                *since = now;
+                Ok(())
+            }
+            Circuit::Open { .. } | Circuit::HalfOpen { .. } => Err(Error::CircuitOpen {
+                key: key.to_string(),
+            }),
+        }
+    }
+
+    pub(crate) fn record(&self, key: &str, success: bool) {
+        let now = Instant::now();
+        let mut circuits = self.circuits.lock().unwrap();
+        let Some(circuit) = circuits.get_mut(key) else {
+            return;
+        };
+
+        match circuit {
+            Circuit::Closed { outcomes } => {
+                outcomes.push_back(success);
+                while outcomes.len() > self.window_size {
+                    outcomes.pop_front();
+                }
+                let failures = outcomes.iter().filter(|ok| !**ok).count();
+                if outcomes.len() >= self.minimum_requests
+                    && failures as f64 >= self.failure_rate * outcomes.len() as f64
+                {
+                    *circuit = Circuit::Open {
+                        until: now + self.open_duration,
+                    };
+                }
+            }
+            Circuit::HalfOpen { .. } if success => {
+                *circuit = Circuit::Closed {
+                    outcomes: VecDeque::new(),
+                };
+            }
+            Circuit::HalfOpen { .. } => {
+                *circuit = Circuit::Open {
+                    until: now + self.open_duration,
+                };
+            }
+            Circuit::Open { .. } => {}
+        }
+    }
+}
+
+impl Circuit {
+    fn state(&self, now: Instant) -> CircuitState {
+        match self {
+            Self::Closed { .. } => CircuitState::Closed,
+            Self::Open { until } if *until <= now => CircuitState::HalfOpen,
+            Self::Open { .. } => CircuitState::Open,
+            Self::HalfOpen { .. } => CircuitState::HalfOpen,
+        }
+    }
+}
+
+// Server errors, overloads and transport failures count against the circuit; client errors
+// such as 400 or 429 say nothing about upstream health.
+pub(crate) fn is_failure(result: Result<StatusCode, &Error>) -> bool {
+    match result {
+        Ok(status) => status.is_server_error(),
+        Err(err) => matches!(
+            err,
+            Error::Timeout | Error::Transport(_) | Error::Connection(_)
+        ),
+    }
+}
@@ -0,0 +1,255 @@
+use crate::error::Error;
+use futures_util::future::BoxFuture;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+// Either field may be set; they become `x-api-key` and `Authorization: Bearer` respectively.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Credentials {
+    pub api_key: Option<String>,
+    pub auth_token: Option<String>,
+    pub expires_at: Option<SystemTime>,
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("api_key", &self.api_key.as_ref().map(|_| "[redacted]"))
+            .field(
+                "auth_token",
+                &self.auth_token.as_ref().map(|_| "[redacted]"),
+            )
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+impl Credentials {
+    pub fn api_key(api_key: impl Into<String>) -> Self {
+        Self {
+            api_key: Some(api_key.into()),
+            ..Self::default()
+        }
+    }
+
+    pub fn auth_token(auth_token: impl Into<String>) -> Self {
+        Self {
+            auth_token: Some(auth_token.into()),
+            ..Self::default()
+        }
+    }
+
+    pub fn expires_at(mut self, expires_at: SystemTime) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+}
+
+// Consulted before every attempt, including retries, so rotated credentials are picked up
+// without rebuilding the client.
+pub trait CredentialProvider: fmt::Debug + Send + Sync {
+    fn credentials(&self) -> BoxFuture<'_, Result<Credentials, Error>>;
+
+    // Called when the API rejects the credentials with 401. Returns whether the next call to
+    // `credentials` may yield something different; only then is the request retried once.
+    fn invalidate(&self) -> bool {
+        false
+    }
+}
+
+impl CredentialProvider for Credentials {
+    fn credentials(&self) -> BoxFuture<'_, Result<Credentials, Error>> {
+        Box::pin(async move { Ok(self.clone()) })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    ApiKey,
+    AuthToken,
+}
+
+impl Kind {
+    fn credentials(self, value: String) -> Credentials {
+        match self {
+            Self::ApiKey => Credentials::api_key(value),
+            Self::AuthToken => Credentials::auth_token(value),
+        }
+    }
+}
+
+// Reads an environment variable on every attempt.
+#[derive(Debug, Clone)]
+pub struct EnvCredentials {
+    var: String,
+    kind: Kind,
+}
+
+impl EnvCredentials {
+    pub fn api_key(var: impl Into<String>) -> Self {
+        Self {
+            var: var.into(),
+            kind: Kind::ApiKey,
+        }
+    }
+
+    pub fn auth_token(var: impl Into<String>) -> Self {
+        Self {
+            var: var.into(),
+            kind: Kind::AuthToken,
+        }
+    }
+}
+
+impl CredentialProvider for EnvCredentials {
+    fn credentials(&self) -> BoxFuture<'_, Result<Credentials, Error>> {
+        Box::pin(async move {
+            match std::env::var(&self.var) {
+                Ok(value) if !value.is_empty() => Ok(self.kind.credentials(value)),
+                _ => Err(Error::Credentials(format!(
+                    "environment variable {} is not set",
+                    self.var
+                ))),
+            }
+        })
+    }
+}
+
+// Reads a secret from a file (e.g. a mounted Kubernetes secret) and re-reads it whenever the
+// file's modification time changes.
+#[derive(Debug)]
+pub struct FileCredentials {
+    path: PathBuf,
+    kind: Kind,
+    cached: Mutex<Option<(SystemTime, String)>>,
+}
+
+impl FileCredentials {
+    pub fn api_key(path: impl Into<PathBuf>) -> Self {
+        Self::new(path.into(), Kind::ApiKey)
+    }
+
+    pub fn auth_token(path: impl Into<PathBuf>) -> Self {
+        Self::new(path.into(), Kind::AuthToken)
+    }
+
+    fn new(path: PathBuf, kind: Kind) -> Self {
+        Self {
+            path,
+            kind,
+            cached: Mutex::new(None),
+        }
+    }
+
+    fn read_error(&self, err: std::io::Error) -> Error {
+        Error::Credentials(format!("failed to read {}: {err}", self.path.display()))
+    }
+}
+
+impl CredentialProvider for FileCredentials {
+    fn credentials(&self) -> BoxFuture<'_, Result<Credentials, Error>> {
+        Box::pin(async move {
+            let modified = tokio::fs::metadata(&self.path)
+                .await
+                .and_then(|m| m.modified())
+                .map_err(|e| self.read_error(e))?;
+            if let Some((at, value)) = self.cached.lock().unwrap().as_ref() {
+                if *at == modified {
+                    return Ok(self.kind.credentials(value.clone()));
+                }
+            }
+
+            let contents = tokio::fs::read_to_string(&self.path)
+                .await
+                .map_err(|e| self.read_error(e))?;
+            let value = contents.trim().to_string();
+            if value.is_empty() {
+                return Err(Error::Credentials(format!(
+                    "{} is empty",
+                    self.path.display()
+                )));
+            }
+            *self.cached.lock().unwrap() = Some((modified, value.clone()));
+            Ok(self.kind.credentials(value))
+        })
+    }
+
+    fn invalidate(&self) -> bool {
+        *self.cached.lock().unwrap() = None;
+        true
+    }
+}
+
+type Fetch = Arc<dyn Fn() -> BoxFuture<'static, Result<Credentials, Error>> + Send + Sync>;
+
+// Caches credentials from `fetch` (e.g. a call to a secrets service) and fetches new ones shortly
+// before `expires_at`, or right away after a 401. Concurrent callers share a single refresh.
+pub struct RefreshingCredentials {
+    fetch: Fetch,
+    refresh_before: Duration,
+    cached: Mutex<Option<Credentials>>,
+    refreshing: futures_util::lock::Mutex<()>,
+}
+
+impl fmt::Debug for RefreshingCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RefreshingCredentials")
+            .field("refresh_before", &self.refresh_before)
+            .field("cached", &self.cached)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RefreshingCredentials {
+    pub fn new<F>(fetch: F) -> Self
+    where
+        F: Fn() -> BoxFuture<'static, Result<Credentials, Error>> + Send + Sync + 'static,
+    {
+        Self {
+            fetch: Arc::new(fetch),
+            refresh_before: Duration::from_secs(60),
+            cached: Mutex::new(None),
+            refreshing: futures_util::lock::Mutex::new(()),
+        }
+    }
+
+    pub fn refresh_before(mut self, refresh_before: Duration) -> Self {
+        self.refresh_before = refresh_before;
+        self
+    }
+
+    fn fresh(&self) -> Option<Credentials> {
+        let cached = self.cached.lock().unwrap();
+        cached
+            .as_ref()
+            .filter(|c| {
+                c.expires_at
+                    .is_none_or(|at| at > SystemTime::now() + self.refresh_before)
+            })
+            .cloned()
+    }
+}
+
+impl CredentialProvider for RefreshingCredentials {
+    fn credentials(&self) -> BoxFuture<'_, Result<Credentials, Error>> {
+        Box::pin(async move {
+            if let Some(credentials) = self.fresh() {
+                return Ok(credentials);
+            }
+            let _guard = self.refreshing.lock().await;
+            if let Some(credentials) = self.fresh() {
+                return Ok(credentials);
+            }
+            let credentials = (self.fetch)().await?;
+            *self.cached.lock().unwrap() = Some(credentials.clone());
+            Ok(credentials)
+        })
+    }
+
+    fn invalidate(&self) -> bool {
+        *self.cached.lock().unwrap() = None;
+        true
+    }
+}
@@ -0,0 +1,235 @@
+use crate::client::HttpRequest;
+use crate::error::Error;
+use opentelemetry::trace::{Span as _, SpanKind, Status, Tracer as _};
+use opentelemetry::{global, Array, KeyValue, StringValue, Value as OtelValue};
+use reqwest::Method;
+use serde_json::Value;
+use std::time::Duration;
+
+const SCOPE: &str = "anthropic-sdk";
+const PROVIDER: &str = "anthropic";
+
+// A single GenAI operation as described by the OpenTelemetry semantic conventions.
+pub(crate) struct Operation {
+    name: &'static str,
+    request_model: String,
+    server_address: Option<String>,
+    server_port: Option<u16>,
+    span: global::BoxedSpan,
+    response_model: Option<String>,
+    response_id: Option<String>,
+    finish_reasons: Vec<String>,
+    input_tokens: Option<u64>,
+    output_tokens: Option<u64>,
+    error_type: Option<String>,
+    first_chunk_seen: bool,
+}
+
+impl Operation {
+    pub fn start(request: &HttpRequest) -> Option<Self> {
+        let path = request.url.path();
+        let name = match request.method {
+            Method::POST if path.ends_with("/v1/messages") => "chat",
+            Method::POST if path.ends_with("/v1/complete") => "text_completion",
+            _ => return None,
+        };
+        let body = serde_json::from_slice::<Value>(request.body.as_ref()?.as_bytes()?).ok()?;
+        let request_model = body.get("model")?.as_str()?.to_string();
+        let server_address = request.url.host_str().map(str::to_string);
+        let server_port = request.url.port_or_known_default();
+
+        let mut attributes = vec![
+            KeyValue::new("gen_ai.operation.name", name),
+            KeyValue::new("gen_ai.provider.name", PROVIDER),
+            KeyValue::new("gen_ai.system", PROVIDER),
+            KeyValue::new("gen_ai.request.model", request_model.clone()),
+        ];
+        for (field, key) in [
+            ("max_tokens", "gen_ai.request.max_tokens"),
+            ("top_k", "gen_ai.request.top_k"),
+        ] {
+            if let Some(value) = body.get(field).and_then(Value::as_i64) {
+                attributes.push(KeyValue::new(key, value));
+            }
+        }
+        for (field, key) in [
+            ("temperature", "gen_ai.request.temperature"),
+            ("top_p", "gen_ai.request.top_p"),
+        ] {
+            if let Some(value) = body.get(field).and_then(Value::as_f64) {
+                attributes.push(KeyValue::new(key, value));
+            }
+        }
+        if let Some(address) = &server_address {
+            attributes.push(KeyValue::new("server.address", address.clone()));
+        }
+        if let Some(port) = server_port {
+            attributes.push(KeyValue::new("server.port", i64::from(port)));
+        }
+
+        let tracer = global::tracer(SCOPE);
+        let span = tracer
+            .span_builder(format!("{name} {request_model}"))
+            .with_kind(SpanKind::Client)
+            .with_attributes(attributes)
+            .start(&tracer);
+
+        Some(Self {
+            name,
+            request_model,
+            server_address,
+            server_port,
+            span,
+            response_model: None,
+            response_id: None,
+            finish_reasons: Vec::new(),
+            input_tokens: None,
+            output_tokens: None,
+            error_type: None,
+            first_chunk_seen: false,
+        })
+    }
+
+    // Accepts a complete message or a `message_start`/`message_delta` stream event.
+    pub fn observe(&mut self, value: &Value) {
+        let message = value.get("message").unwrap_or(value);
+        if let Some(model) = message.get("model").and_then(Value::as_str) {
+            self.response_model = Some(model.to_string());
+        }
+        if let Some(id) = message.get("id").and_then(Value::as_str) {
+            self.response_id = Some(id.to_string());
+        }
+        let stop_reason = message
+            .get("stop_reason")
+            .or_else(|| value.get("delta").and_then(|d| d.get("stop_reason")))
+            .and_then(Value::as_str);
+        if let Some(reason) = stop_reason {
+            self.finish_reasons = vec![reason.to_string()];
+        }
+
+        let Some(usage) = message.get("usage").or_else(|| value.get("usage")) else {
+            return;
+        };
+        // Cached prompt tokens are reported separately by the API but are still input tokens.
+        let input = [
+            "input_tokens",
+            "cache_creation_input_tokens",
+            "cache_read_input_tokens",
+        ]
+        .iter()
+        .filter_map(|field| usage.get(*field).and_then(Value::as_u64))
+        .reduce(|a, b| a + b);
+        if input.is_some() {
+            self.input_tokens = input;
+        }
+        if let Some(output) = usage.get("output_tokens").and_then(Value::as_u64) {
+            self.output_tokens = Some(output);
+        }
+    }
+
+    pub fn first_chunk(&mut self, elapsed: Duration) {
+        if self.first_chunk_seen {
+            return;
+        }
+        self.first_chunk_seen = true;
+        global::meter(SCOPE)
+            .f64_histogram("gen_ai.client.operation.time_to_first_chunk")
+            .with_unit("s")
+            .with_description("Time to receive the first chunk of a streamed response")
+            .build()
+            .record(elapsed.as_secs_f64(), &self.metric_attributes());
+    }
+
+    pub fn fail(&mut self, err: &Error) {
+        let error_type = match err {
+            Error::Http(http) => http
+                .kind()
+                .map(|kind| kind.as_str().to_string())
+                .or_else(|| http.api_error().status.map(|s| s.as_u16().to_string())),
+            Error::Timeout => Some("timeout".to_string()),
+            Error::CircuitOpen { .. } => Some("circuit_open".to_string()),
+            Error::Aborted => Some("aborted".to_string()),
+            _ => None,
+        };
+        self.error_type = Some(error_type.unwrap_or_else(|| "_OTHER".to_string()));
+        self.span.set_status(Status::error(err.to_string()));
+    }
+
+    pub fn end(mut self, duration: Duration) {
+        let attributes = self.metric_attributes();
+        let meter = global::meter(SCOPE);
+        meter
+            .f64_histogram("gen_ai.client.operation.duration")
+            .with_unit("s")
+            .with_description("GenAI operation duration")
+            .build()
+            .record(duration.as_secs_f64(), &attributes);
+
+        let token_usage = meter
+            .u64_histogram("gen_ai.client.token.usage")
+            .with_unit("{token}")
+            .with_description("Measures number of input and output tokens used")
+            .build();
+        for (token_type, tokens) in [("input", self.input_tokens), ("output", self.output_tokens)] {
+            if let Some(tokens) = tokens {
+                let mut attributes = attributes.clone();
+                attributes.push(KeyValue::new("gen_ai.token.type", token_type));
+                token_usage.record(tokens, &attributes);
+            }
+        }
+
+        if let Some(model) = self.response_model.take() {
+            self.span
+                .set_attribute(KeyValue::new("gen_ai.response.model", model));
+        }
+        if let Some(id) = self.response_id.take() {
+            self.span
+                .set_attribute(KeyValue::new("gen_ai.response.id", id));
+        }
+        if !self.finish_reasons.is_empty() {
+            let reasons = std::mem::take(&mut self.finish_reasons)
+                .into_iter()
+                .map(StringValue::from)
+                .collect();
+            self.span.set_attribute(KeyValue::new(
+                "gen_ai.response.finish_reasons",
+                OtelValue::Array(Array::String(reasons)),
+            ));
+        }
+        if let Some(tokens) = self.input_tokens {
+            self.span
+                .set_attribute(KeyValue::new("gen_ai.usage.input_tokens", tokens as i64));
+        }
+        if let Some(tokens) = self.output_tokens {
+            self.span
+                .set_attribute(KeyValue::new("gen_ai.usage.output_tokens", tokens as i64));
+        }
+        if let Some(error_type) = self.error_type.take() {
+            self.span
+                .set_attribute(KeyValue::new("error.type", error_type));
+        }
+        self.span.end();
+    }
+
+    fn metric_attributes(&self) -> Vec<KeyValue> {
+        let mut attributes = vec![
+            KeyValue::new("gen_ai.operation.name", self.name),
+            KeyValue::new("gen_ai.provider.name", PROVIDER),
+            KeyValue::new("gen_ai.system", PROVIDER),
+            KeyValue::new("gen_ai.request.model", self.request_model.clone()),
+        ];
+        if let Some(model) = &self.response_model {
+            attributes.push(KeyValue::new("gen_ai.response.model", model.clone()));
+        }
+        if let Some(address) = &self.server_address {
+            attributes.push(KeyValue::new("server.address", address.clone()));
+        }
+        if let Some(port) = self.server_port {
+            attributes.push(KeyValue::new("server.port", i64::from(port)));
+        }
+        if let Some(error_type) = &self.error_type {
+            attributes.push(KeyValue::new("error.type", error_type.clone()));
+        }
+        attributes
+    }
+}
@@ -0,0 +1,69 @@
+use crate::error::Error;
+use reqwest::{Certificate, Client as HttpClient, Proxy};
+use std::net::IpAddr;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Default)]
+pub struct HttpConfig {
+    pub proxies: Vec<Proxy>,
+    pub no_proxy: bool,
+    pub root_certificates: Vec<Certificate>,
+    pub tls_built_in_root_certs: Option<bool>,
+    pub connect_timeout: Option<Duration>,
+    pub pool_idle_timeout: Option<Duration>,
+    pub pool_max_idle_per_host: Option<usize>,
+    pub http2_prior_knowledge: bool,
+    pub tcp_keepalive: Option<Duration>,
+    pub tcp_nodelay: Option<bool>,
+    pub local_address: Option<IpAddr>,
+}
+
+impl HttpConfig {
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.proxies.push(proxy);
+        self
+    }
+
+    pub fn root_certificate(mut self, certificate: Certificate) -> Self {
+        self.root_certificates.push(certificate);
+        self
+    }
+
+    pub(crate) fn build_client(&self) -> Result<HttpClient, Error> {
+        let mut builder = HttpClient::builder();
+        for proxy in &self.proxies {
+            builder = builder.proxy(proxy.clone());
+        }
+        if self.no_proxy {
+            builder = builder.no_proxy();
+        }
+        for certificate in &self.root_certificates {
+            builder = builder.add_root_certificate(certificate.clone());
+        }
+        if let Some(enabled) = self.tls_built_in_root_certs {
+            builder = builder.tls_built_in_root_certs(enabled);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(timeout);
+        }
+        if let Some(max) = self.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        if self.http2_prior_knowledge {
+            builder = builder.http2_prior_knowledge();
+        }
+        if let Some(keepalive) = self.tcp_keepalive {
+            builder = builder.tcp_keepalive(keepalive);
+        }
+        if let Some(nodelay) = self.tcp_nodelay {
+            builder = builder.tcp_nodelay(nodelay);
+        }
+        if let Some(addr) = self.local_address {
+            builder = builder.local_address(addr);
+        }
+        Ok(builder.build()?)
+    }
+}
@@ -1,33 +1,56 @@
+mod builder;
+mod circuit_breaker;
+mod credentials;
+#[cfg(feature = "otel")]
+mod gen_ai;
+mod http_config;
+pub(crate) mod multipart;
+mod rate_limit;
+mod retry;
+mod telemetry;
+mod transport;
+
+pub use crate::client::builder::ClientBuilder;
+pub use crate::client::circuit_breaker::{CircuitBreaker, CircuitScope, CircuitState};
+pub use crate::client::credentials::{
+    CredentialProvider, Credentials, EnvCredentials, FileCredentials, RefreshingCredentials,
+};
+pub use crate::client::http_config::HttpConfig;
+pub use crate::client::rate_limit::{RateLimitBucket, RateLimitInfo, RateLimiter};
+pub use crate::client::retry::RetryPolicy;
+pub use crate::client::transport::{
+    BodyStream, HttpBody, HttpRequest, HttpResponse, HttpTransport, ReqwestTransport,
+};
+
+use crate::client::multipart::MultipartForm;
+use crate::client::telemetry::RequestSpan;
 use crate::error::{ApiError, Error, HttpApiError};
+use crate::middleware::{Middleware, Next};
 use crate::resources::{beta::Beta, completions::Completions, messages::Messages, models::Models};
 use crate::streaming::{RawStream, SseEvent, SseParser};
 use bytes::Bytes;
-use futures_util::stream::BoxStream;
 use futures_util::StreamExt;
-use httpdate::parse_http_date;
 use reqwest::header::{
-    HeaderMap, HeaderName, HeaderValue, ACCEPT, AUTHORIZATION, CONTENT_TYPE, USER_AGENT,
+    HeaderMap, HeaderName, HeaderValue, ACCEPT, AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE,
+    USER_AGENT,
 };
-use reqwest::multipart::Form;
-use reqwest::{Client as HttpClient, Method, Response, StatusCode, Url};
+use reqwest::{Client as HttpClient, Method, StatusCode, Url};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use serde_json::Value;
 use std::sync::Arc;
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, Instant};
 
 const DEFAULT_BASE_URL: &str = "https://api.anthropic.com";
 const ANTHROPIC_VERSION: &str = "2023-06-01";
 
-const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+pub(crate) const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10 * 60);
 const DEFAULT_MAX_RETRIES: u32 = 2;
 
 const HEADER_ANTHROPIC_VERSION: HeaderName = HeaderName::from_static("anthropic-version");
+const HEADER_IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
 const HEADER_REQUEST_ID: HeaderName = HeaderName::from_static("request-id");
-const HEADER_RETRY_AFTER: HeaderName = HeaderName::from_static("retry-after");
-const HEADER_RETRY_AFTER_MS: HeaderName = HeaderName::from_static("retry-after-ms");
 const HEADER_X_API_KEY: HeaderName = HeaderName::from_static("x-api-key");
-const HEADER_X_SHOULD_RETRY: HeaderName = HeaderName::from_static("x-should-retry");
 const HEADER_X_STAINLESS_RETRY_COUNT: HeaderName =
     HeaderName::from_static("x-stainless-retry-count");
 const HEADER_X_STAINLESS_TIMEOUT: HeaderName = HeaderName::from_static("x-stainless-timeout");
@@ -36,10 +59,39 @@ const HEADER_X_STAINLESS_TIMEOUT: HeaderName = HeaderName::from_static("x-stainl
 pub struct ClientOptions {
     pub api_key: Option<String>,
     pub auth_token: Option<String>,
+    pub credential_provider: Option<Arc<dyn CredentialProvider>>,
     pub base_url: Option<String>,
     pub timeout: Option<Duration>,
     pub max_retries: Option<u32>,
+    pub retry_policy: Option<RetryPolicy>,
     pub default_headers: HeaderMap,
+    pub http_client: Option<HttpClient>,
+    pub http: Option<HttpConfig>,
+    pub transport: Option<Arc<dyn HttpTransport>>,
+    pub middleware: Vec<Arc<dyn Middleware>>,
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    pub circuit_breaker: Option<Arc<CircuitBreaker>>,
+}
+
+impl ClientOptions {
+    pub fn empty() -> Self {
+        Self {
+            api_key: None,
+            auth_token: None,
+            credential_provider: None,
+            base_url: None,
+            timeout: None,
+            max_retries: None,
+            retry_policy: None,
+            default_headers: HeaderMap::new(),
+            http_client: None,
+            http: None,
+            transport: None,
+            middleware: Vec::new(),
+            rate_limiter: None,
+            circuit_breaker: None,
+        }
+    }
 }
 
 impl Default for ClientOptions {
@@ -50,10 +102,18 @@ impl Default for ClientOptions {
         Self {
             api_key,
             auth_token,
+            credential_provider: None,
             base_url,
             timeout: None,
             max_retries: None,
+            retry_policy: None,
             default_headers: HeaderMap::new(),
+            http_client: None,
+            http: None,
+            transport: None,
+            middleware: Vec::new(),
+            rate_limiter: None,
+            circuit_breaker: None,
         }
     }
 }
@@ -62,8 +122,11 @@ impl Default for ClientOptions {
 pub struct RequestOptions {
     pub timeout: Option<Duration>,
     pub max_retries: Option<u32>,
+    pub retry_policy: Option<RetryPolicy>,
     pub headers: HeaderMap,
     pub remove_headers: Vec<HeaderName>,
+    pub middleware: Vec<Arc<dyn Middleware>>,
+    pub idempotency_key: Option<String>,
 }
 
 impl RequestOptions {
@@ -76,6 +139,21 @@ impl RequestOptions {
         self.headers.insert(name, value);
         self
     }
+
+    pub fn middleware(mut self, middleware: Arc<dyn Middleware>) -> Self {
+        self.middleware.push(middleware);
+        self
+    }
+
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    pub fn idempotency_key(mut self, key: impl Into<String>) -> Self {
+        self.idempotency_key = Some(key.into());
+        self
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -84,6 +162,13 @@ pub struct ApiResponse<T> {
     pub request_id: Option<String>,
     pub status: StatusCode,
     pub headers: HeaderMap,
+    pub idempotency_key: Option<String>,
+}
+
+impl<T> ApiResponse<T> {
+    pub fn rate_limit(&self) -> RateLimitInfo {
+        RateLimitInfo::from_headers(&self.headers)
+    }
 }
 
 #[derive(Clone)]
@@ -92,36 +177,59 @@ pub struct Anthropic {
     pub models: Models,
     pub completions: Completions,
     pub beta: Beta,
+    pub(crate) inner: Arc<Inner>,
 }
 
 impl Anthropic {
     pub fn new(options: ClientOptions) -> Result<Self, Error> {
-        let inner = Arc::new(Inner::new(options)?);
-        Ok(Self {
+        Ok(Self::from_inner(Inner::new(options)?))
+    }
+
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::new()
+    }
+
+    // Fields left as `None` (or empty headers) keep the current client's values, middleware is
+    // appended to the current chain, and the underlying connection pool is shared unless a new
+    // `http_client` is given. Build the overrides from `ClientOptions::empty()`: `default()` fills
+    // in the key, token and base URL from the environment, which would replace the client's own.
+    pub fn with_options(&self, options: ClientOptions) -> Result<Self, Error> {
+        Ok(Self::from_inner(self.inner.with_overrides(options)?))
+    }
+
+    fn from_inner(inner: Inner) -> Self {
+        let inner = Arc::new(inner);
+        Self {
             messages: Messages::new(inner.clone()),
             models: Models::new(inner.clone()),
             completions: Completions::new(inner.clone()),
             beta: Beta::new(inner.clone()),
-        })
-    }
-
-    pub fn with_options(&self, options: ClientOptions) -> Result<Self, Error> {
-        Self::new(options)
+            inner,
+        }
     }
 }
 
+// A successful response together with the span and idempotency key of the call that produced it.
+type Sent = (HttpResponse, RequestSpan, Option<String>);
+
 pub(crate) struct Inner {
-    http: HttpClient,
+    transport: Arc<dyn HttpTransport>,
+    middleware: Vec<Arc<dyn Middleware>>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    circuit_breaker: Option<Arc<CircuitBreaker>>,
     base_url: Url,
     timeout: Duration,
     timeout_is_default: bool,
     max_retries: u32,
+    retry_policy: RetryPolicy,
 
     api_key: Option<String>,
     auth_token: Option<String>,
+    credential_provider: Option<Arc<dyn CredentialProvider>>,
 
     default_headers: HeaderMap,
     user_agent: HeaderValue,
+    auth_required: bool,
 }
 
 impl Inner {
@@ -133,20 +241,108 @@ impl Inner {
         let timeout_is_default = options.timeout.is_none();
         let max_retries = options.max_retries.unwrap_or(DEFAULT_MAX_RETRIES);
 
-        let http = HttpClient::builder().build()?;
+        let transport = match options.transport {
+            Some(transport) => transport,
+            None => Arc::new(ReqwestTransport::new(
+                http_client(&options)?.unwrap_or_default(),
+            )),
+        };
         let user_agent =
             HeaderValue::from_str(&format!("anthropic-sdk-rust/{}", env!("CARGO_PKG_VERSION")))?;
 
         Ok(Self {
-            http,
+            transport,
+            middleware: options.middleware,
+            rate_limiter: options.rate_limiter,
+            circuit_breaker: options.circuit_breaker,
             base_url,
             timeout,
             timeout_is_default,
             max_retries,
+            retry_policy: options.retry_policy.unwrap_or_default(),
             api_key: options.api_key,
             auth_token: options.auth_token,
+            credential_provider: options.credential_provider,
             default_headers: options.default_headers,
             user_agent,
+            auth_required: true,
+        })
+    }
+
+    // For backends that authenticate each request inside a transport wrapped around the
+    // configured one (e.g. by signing it) rather than with an API key.
+    #[cfg_attr(not(any(feature = "bedrock", feature = "vertex")), allow(dead_code))]
+    pub(crate) fn with_transport_auth<F>(mut options: ClientOptions, wrap: F) -> Result<Self, Error>
+    where
+        F: FnOnce(Arc<dyn HttpTransport>) -> Arc<dyn HttpTransport>,
+    {
+        let transport = Self::base_transport(&mut options)?;
+        options.transport = Some(wrap(transport));
+        Ok(Self {
+            auth_required: false,
+            ..Self::new(options)?
+        })
+    }
+
+    // The transport requests would go out on: the configured one, or a reqwest client built from
+    // the HTTP settings, which are consumed.
+    #[cfg_attr(not(any(feature = "bedrock", feature = "vertex")), allow(dead_code))]
+    pub(crate) fn base_transport(
+        options: &mut ClientOptions,
+    ) -> Result<Arc<dyn HttpTransport>, Error> {
+        let transport = match options.transport.take() {
+            Some(transport) => transport,
+            None => Arc::new(ReqwestTransport::new(
+                http_client(options)?.unwrap_or_default(),
+            )),
+        };
+        options.http_client = None;
+        options.http = None;
+        Ok(transport)
+    }
+
+    fn with_overrides(&self, options: ClientOptions) -> Result<Self, Error> {
+        let base_url = match options.base_url.as_deref() {
+            Some(base_url) => Url::parse(base_url)?,
+            None => self.base_url.clone(),
+        };
+
+        let mut default_headers = self.default_headers.clone();
+        for (name, value) in options.default_headers.iter() {
+            default_headers.insert(name, value.clone());
+        }
+
+        let transport = match (&options.transport, http_client(&options)?) {
+            (Some(transport), _) => transport.clone(),
+            (None, Some(http)) => Arc::new(ReqwestTransport::new(http)),
+            (None, None) => self.transport.clone(),
+        };
+
+        let mut middleware = self.middleware.clone();
+        middleware.extend(options.middleware);
+
+        Ok(Self {
+            transport,
+            middleware,
+            rate_limiter: options.rate_limiter.or_else(|| self.rate_limiter.clone()),
+            circuit_breaker: options
+                .circuit_breaker
+                .or_else(|| self.circuit_breaker.clone()),
+            base_url,
+            timeout: options.timeout.unwrap_or(self.timeout),
+            timeout_is_default: options.timeout.is_none() && self.timeout_is_default,
+            max_retries: options.max_retries.unwrap_or(self.max_retries),
+            retry_policy: options
+                .retry_policy
+                .unwrap_or_else(|| self.retry_policy.clone()),
+            api_key: options.api_key.or_else(|| self.api_key.clone()),
+            auth_token: options.auth_token.or_else(|| self.auth_token.clone()),
+            credential_provider: options
+                .credential_provider
+                .or_else(|| self.credential_provider.clone()),
+            default_headers,
+            user_agent: self.user_agent.clone(),
+            auth_required: self.auth_required,
         })
     }
 
@@ -166,63 +362,7 @@ impl Inner {
         Ok(self.base_url.join(path)?)
     }
 
-    fn should_retry_status_headers(status: StatusCode, headers: &HeaderMap) -> bool {
-        if let Some(value) = headers
-            .get(HEADER_X_SHOULD_RETRY)
-            .and_then(|v| v.to_str().ok())
-        {
-            if value == "true" {
-                return true;
-            }
-            if value == "false" {
-                return false;
-            }
-        }
-
-        matches!(
-            status,
-            StatusCode::REQUEST_TIMEOUT | StatusCode::CONFLICT | StatusCode::TOO_MANY_REQUESTS
-        ) || status.as_u16() >= 500
-    }
-
-    fn retry_delay_from_headers(headers: &HeaderMap) -> Option<Duration> {
-        if let Some(ms) = headers
-            .get(HEADER_RETRY_AFTER_MS)
-            .and_then(|v| v.to_str().ok())
-        {
-            if let Ok(ms) = ms.parse::<f64>() {
-                if ms.is_finite() && ms >= 0.0 {
-                    return Some(Duration::from_millis(ms as u64));
-                }
-            }
-        }
-
-        let ra = headers.get(HEADER_RETRY_AFTER)?.to_str().ok()?;
-        if let Ok(seconds) = ra.parse::<f64>() {
-            if seconds.is_finite() && seconds >= 0.0 {
-                return Some(Duration::from_millis((seconds * 1000.0) as u64));
-            }
-            return None;
-        }
-
-        // HTTP-date
-        let at = parse_http_date(ra).ok()?;
-        let now = SystemTime::now();
-        let wait = at.duration_since(now).ok()?;
-        Some(wait)
-    }
-
-    fn default_retry_delay(retries_remaining: u32, max_retries: u32) -> Duration {
-        let initial = 0.5_f64;
-        let max_delay = 8.0_f64;
-
-        let num_retries = max_retries.saturating_sub(retries_remaining);
-        let sleep_seconds = (initial * 2.0_f64.powi(num_retries as i32)).min(max_delay);
-        let jitter = 1.0 - fastrand::f64() * 0.25;
-        Duration::from_millis((sleep_seconds * jitter * 1000.0) as u64)
-    }
-
-    fn make_headers(
+    async fn make_headers(
         &self,
         retry_count: u32,
         timeout: Duration,
@@ -244,10 +384,19 @@ impl Inner {
             HeaderValue::from_str(&timeout.as_secs().to_string())?,
         );
 
-        if let Some(api_key) = &self.api_key {
+        // A provider replaces the key and token given at construction.
+        let credentials = match &self.credential_provider {
+            Some(provider) => provider.credentials().await?,
+            None => Credentials {
+                api_key: self.api_key.clone(),
+                auth_token: self.auth_token.clone(),
+                expires_at: None,
+            },
+        };
+        if let Some(api_key) = &credentials.api_key {
             headers.insert(HEADER_X_API_KEY, HeaderValue::from_str(api_key)?);
         }
-        if let Some(auth_token) = &self.auth_token {
+        if let Some(auth_token) = &credentials.auth_token {
             headers.insert(
                 AUTHORIZATION,
                 HeaderValue::from_str(&format!("Bearer {auth_token}"))?,
@@ -265,7 +414,10 @@ impl Inner {
             headers.insert(name, value.clone());
         }
 
-        if headers.get(HEADER_X_API_KEY).is_none() && headers.get(AUTHORIZATION).is_none() {
+        if self.auth_required
+            && headers.get(HEADER_X_API_KEY).is_none()
+            && headers.get(AUTHORIZATION).is_none()
+        {
             return Err(Error::AuthMissing);
         }
         Ok(headers)
@@ -283,25 +435,10 @@ impl Inner {
         T: DeserializeOwned,
         B: Serialize + ?Sized,
     {
-        let response = self
-            .request_raw(method, path_or_url, query, body, options.clone())
+        let (response, span, idempotency_key) = self
+            .send_json(method, path_or_url, query, body, options)
             .await?;
-
-        let status = response.status();
-        let headers = response.headers().clone();
-        let request_id = headers
-            .get(HEADER_REQUEST_ID)
-            .and_then(|v| v.to_str().ok())
-            .map(|s| s.to_string());
-
-        let bytes = response.bytes().await?;
-        let data = serde_json::from_slice::<T>(&bytes)?;
-        Ok(ApiResponse {
-            data,
-            request_id,
-            status,
-            headers,
-        })
+        Self::json_response(response, span, idempotency_key).await
     }
 
     pub async fn request_multipart_json<T, F>(
@@ -314,124 +451,66 @@ impl Inner {
     ) -> Result<ApiResponse<T>, Error>
     where
         T: DeserializeOwned,
-        F: Fn() -> Result<Form, Error> + Send + Sync,
+        F: Fn() -> Result<MultipartForm, Error> + Send + Sync,
     {
-        let response = self
-            .request_multipart_raw(method, path_or_url, query, build_form, options.clone())
+        let (response, span, idempotency_key) = self
+            .send_multipart(method, path_or_url, query, build_form, options)
             .await?;
+        Self::json_response(response, span, idempotency_key).await
+    }
 
-        let status = response.status();
-        let headers = response.headers().clone();
-        let request_id = headers
-            .get(HEADER_REQUEST_ID)
-            .and_then(|v| v.to_str().ok())
-            .map(|s| s.to_string());
+    async fn json_response<T>(
+        response: HttpResponse,
+        span: RequestSpan,
+        idempotency_key: Option<String>,
+    ) -> Result<ApiResponse<T>, Error>
+    where
+        T: DeserializeOwned,
+    {
+        let status = response.status;
+        let headers = response.headers.clone();
+        let request_id = request_id(&headers);
 
         let bytes = response.bytes().await?;
-        let data = serde_json::from_slice::<T>(&bytes)?;
+        span.observe(&bytes);
+        let data = match serde_json::from_slice::<T>(&bytes) {
+            Ok(data) => data,
+            Err(e) => {
+                let err = Error::Json(e);
+                span.failed(&err);
+                return Err(err);
+            }
+        };
         Ok(ApiResponse {
             data,
             request_id,
             status,
             headers,
+            idempotency_key,
         })
     }
 
-    pub async fn request_multipart_raw<F>(
+    async fn send_multipart<F>(
         &self,
         method: Method,
         path_or_url: &str,
         query: Option<Vec<(String, String)>>,
         build_form: F,
         options: RequestOptions,
-    ) -> Result<Response, Error>
+    ) -> Result<Sent, Error>
     where
-        F: Fn() -> Result<Form, Error> + Send + Sync,
+        F: Fn() -> Result<MultipartForm, Error> + Send + Sync,
     {
-        let timeout = options.timeout.unwrap_or(self.timeout);
-        let max_retries = options.max_retries.unwrap_or(self.max_retries);
-
-        let mut url = self.build_url(path_or_url)?;
-        if let Some(pairs) = query {
-            if !pairs.is_empty() {
-                let mut qp = url.query_pairs_mut();
-                for (k, v) in pairs {
-                    qp.append_pair(&k, &v);
-                }
-            }
-        }
-
-        let mut retries_remaining = max_retries;
-        loop {
-            let retry_count = max_retries.saturating_sub(retries_remaining);
-            let headers = self.make_headers(retry_count, timeout, &options)?;
+        self.execute(method, path_or_url, query, options, || {
             let form = build_form()?;
-
-            let req = self
-                .http
-                .request(method.clone(), url.clone())
-                .headers(headers)
-                .multipart(form);
-
-            let response = tokio::time::timeout(timeout, req.send()).await;
-            match response {
-                Err(_) => {
-                    if retries_remaining > 0 {
-                        let delay = Self::default_retry_delay(retries_remaining, max_retries);
-                        tokio::time::sleep(delay).await;
-                        retries_remaining -= 1;
-                        continue;
-                    }
-                    return Err(Error::Timeout);
-                }
-                Ok(resp) => match resp {
-                    Ok(resp) => {
-                        if resp.status().is_success() {
-                            return Ok(resp);
-                        }
-
-                        let status = resp.status();
-                        let headers = resp.headers().clone();
-                        let should_retry = retries_remaining > 0
-                            && Self::should_retry_status_headers(status, &headers);
-
-                        let body_bytes = resp.bytes().await.unwrap_or_default();
-                        let json = serde_json::from_slice::<Value>(&body_bytes).ok();
-                        let text = String::from_utf8_lossy(&body_bytes).to_string();
-                        let message = extract_error_message(json.as_ref(), &text);
-                        let api_err = ApiError::new(Some(status), Some(&headers), json, message);
-                        let http_err = HttpApiError::from_status(Some(status), api_err);
-
-                        if should_retry {
-                            let delay = Self::retry_delay_from_headers(&headers)
-                                .filter(|d| *d < Duration::from_secs(60))
-                                .unwrap_or_else(|| {
-                                    Self::default_retry_delay(retries_remaining, max_retries)
-                                });
-                            tokio::time::sleep(delay).await;
-                            retries_remaining -= 1;
-                            continue;
-                        }
-
-                        return Err(Error::Http(http_err));
-                    }
-                    Err(err) => {
-                        let is_timeout = err.is_timeout();
-                        if retries_remaining > 0 {
-                            let delay = Self::default_retry_delay(retries_remaining, max_retries);
-                            tokio::time::sleep(delay).await;
-                            retries_remaining -= 1;
-                            continue;
-                        }
-
-                        if is_timeout {
-                            return Err(Error::Timeout);
-                        }
-                        return Err(Error::Transport(err));
-                    }
-                },
+            let mut headers = HeaderMap::new();
+            headers.insert(CONTENT_TYPE, form.content_type());
+            if let Some(len) = form.content_length() {
+                headers.insert(CONTENT_LENGTH, HeaderValue::from(len));
             }
-        }
+            Ok(Some((headers, form.into_body())))
+        })
+        .await
     }
 
     pub async fn request_raw<B>(
@@ -441,15 +520,54 @@ impl Inner {
         query: Option<Vec<(String, String)>>,
         body: Option<&B>,
         options: RequestOptions,
-    ) -> Result<Response, Error>
+    ) -> Result<HttpResponse, Error>
+    where
+        B: Serialize + ?Sized,
+    {
+        let (response, _, _) = self
+            .send_json(method, path_or_url, query, body, options)
+            .await?;
+        Ok(response)
+    }
+
+    async fn send_json<B>(
+        &self,
+        method: Method,
+        path_or_url: &str,
+        query: Option<Vec<(String, String)>>,
+        body: Option<&B>,
+        options: RequestOptions,
+    ) -> Result<Sent, Error>
     where
         B: Serialize + ?Sized,
     {
         let body_bytes = match body {
-            Some(b) => Some(serde_json::to_vec(b)?),
+            Some(b) => Some(Bytes::from(serde_json::to_vec(b)?)),
             None => None,
         };
 
+        self.execute(method, path_or_url, query, options, || {
+            Ok(body_bytes.as_ref().map(|bytes| {
+                let mut headers = HeaderMap::new();
+                headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+                (headers, HttpBody::Bytes(bytes.clone()))
+            }))
+        })
+        .await
+    }
+
+    async fn execute<F>(
+        &self,
+        method: Method,
+        path_or_url: &str,
+        query: Option<Vec<(String, String)>>,
+        options: RequestOptions,
+        build_body: F,
+    ) -> Result<Sent, Error>
+    where
+        // Returns the body together with the headers that describe it.
+        F: Fn() -> Result<Option<(HeaderMap, HttpBody)>, Error> + Send + Sync,
+    {
         let timeout = options.timeout.unwrap_or(self.timeout);
         let max_retries = options.max_retries.unwrap_or(self.max_retries);
 
@@ -463,80 +581,155 @@ impl Inner {
             }
         }
 
+        let span = RequestSpan::new(&method, &url);
+        let idempotency_key = idempotency_key(&method, &options);
+
+        // Client-wide middleware runs outermost, followed by any registered for this request.
+        let mut middleware = self.middleware.clone();
+        middleware.extend(options.middleware.iter().cloned());
+
+        let policy = options.retry_policy.as_ref().unwrap_or(&self.retry_policy);
+        let started = Instant::now();
+        // Retrying is only worthwhile if the backoff sleep still ends inside the overall deadline.
+        let within_deadline = |delay: Duration| {
+            policy
+                .deadline
+                .is_none_or(|d| started.elapsed() + delay < d)
+        };
+
         let mut retries_remaining = max_retries;
+        let mut reauthenticated = false;
+        let mut last_err = None;
         loop {
             let retry_count = max_retries.saturating_sub(retries_remaining);
-            let mut headers = self.make_headers(retry_count, timeout, &options)?;
-            if body_bytes.is_some() {
-                headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            let mut headers = self.make_headers(retry_count, timeout, &options).await?;
+            if let Some(key) = &idempotency_key {
+                headers.insert(HEADER_IDEMPOTENCY_KEY, HeaderValue::from_str(key)?);
+            }
+            let body = match build_body() {
+                Ok(Some((body_headers, body))) => {
+                    headers.extend(body_headers);
+                    Some(body)
+                }
+                Ok(None) => None,
+                // A body that can't be replayed, such as a one-shot upload stream, ends the
+                // retries; the failure that prompted them is the one worth reporting.
+                Err(err) => {
+                    let err = last_err.unwrap_or(err);
+                    span.failed(&err);
+                    return Err(err);
+                }
+            };
+
+            let request = HttpRequest {
+                method: method.clone(),
+                url: url.clone(),
+                headers,
+                body,
+            };
+
+            span.attempt(&request, retry_count);
+
+            let circuit = match &self.circuit_breaker {
+                Some(breaker) => {
+                    let key = breaker.key(&request);
+                    if let Err(err) = breaker.acquire(&key) {
+                        span.failed(&err);
+                        return Err(err);
+                    }
+                    Some((breaker, key))
+                }
+                None => None,
+            };
+
+            if let Some(limiter) = &self.rate_limiter {
+                let input_tokens = limiter.estimate_input_tokens(&request).await;
+                limiter.acquire(input_tokens).await;
             }
 
-            let mut req = self
-                .http
-                .request(method.clone(), url.clone())
-                .headers(headers);
-            if let Some(bytes) = &body_bytes {
-                req = req.body(bytes.clone());
+            let sent = Next::new(&middleware, &*self.transport).run(request);
+            let result = tokio::time::timeout(timeout, sent).await;
+            if let Ok(Ok(resp)) = &result {
+                span.response(resp.status, &resp.headers);
+            }
+            if let (Some(limiter), Ok(Ok(resp))) = (&self.rate_limiter, &result) {
+                limiter.observe(resp.status, &resp.headers);
             }
+            if let Some((breaker, key)) = &circuit {
+                let failed = match &result {
+                    Err(_) => true,
+                    Ok(Ok(resp)) => circuit_breaker::is_failure(Ok(resp.status)),
+                    Ok(Err(err)) => circuit_breaker::is_failure(Err(err)),
+                };
+                breaker.record(key, !failed);
+            }
+
+            let err = match result {
+                Err(_) => Error::Timeout,
+                Ok(Ok(resp)) if resp.status.is_success() => {
+                    return Ok((resp, span, idempotency_key))
+                }
+                Ok(Ok(resp)) => {
+                    let status = resp.status;
+                    let headers = resp.headers.clone();
+                    let delay = policy.delay(retry_count, Some(&headers));
+                    let should_retry = retries_remaining > 0
+                        && policy.should_retry_status(status, &headers)
+                        && within_deadline(delay);
+
+                    let body_bytes = resp.bytes().await.unwrap_or_default();
+                    let json = serde_json::from_slice::<Value>(&body_bytes).ok();
+                    let text = String::from_utf8_lossy(&body_bytes).to_string();
+                    let message = extract_error_message(json.as_ref(), &text);
+                    let mut api_err = ApiError::new(Some(status), Some(&headers), json, message);
+                    api_err.idempotency_key = idempotency_key.clone();
+                    let http_err = HttpApiError::from_status(Some(status), api_err);
+
+                    // Credentials may have been rotated under us; refresh them and try once more
+                    // without spending a retry.
+                    let refreshed = status == StatusCode::UNAUTHORIZED
+                        && !reauthenticated
+                        && self
+                            .credential_provider
+                            .as_ref()
+                            .is_some_and(|provider| provider.invalidate());
+                    if refreshed {
+                        reauthenticated = true;
+                        span.retry(retry_count, Duration::ZERO, &http_err);
+                        last_err = Some(Error::Http(http_err));
+                        continue;
+                    }
 
-            let response = tokio::time::timeout(timeout, req.send()).await;
-            match response {
-                Err(_) => {
-                    if retries_remaining > 0 {
-                        let delay = Self::default_retry_delay(retries_remaining, max_retries);
+                    if should_retry {
+                        span.retry(retry_count + 1, delay, &http_err);
                         tokio::time::sleep(delay).await;
                         retries_remaining -= 1;
+                        last_err = Some(Error::Http(http_err));
                         continue;
                     }
-                    return Err(Error::Timeout);
-                }
-                Ok(resp) => match resp {
-                    Ok(resp) => {
-                        if resp.status().is_success() {
-                            return Ok(resp);
-                        }
-
-                        let status = resp.status();
-                        let headers = resp.headers().clone();
-                        let should_retry = retries_remaining > 0
-                            && Self::should_retry_status_headers(status, &headers);
-
-                        let body_bytes = resp.bytes().await.unwrap_or_default();
-                        let json = serde_json::from_slice::<Value>(&body_bytes).ok();
-                        let text = String::from_utf8_lossy(&body_bytes).to_string();
-                        let message = extract_error_message(json.as_ref(), &text);
-                        let api_err = ApiError::new(Some(status), Some(&headers), json, message);
-                        let http_err = HttpApiError::from_status(Some(status), api_err);
-
-                        if should_retry {
-                            let delay = Self::retry_delay_from_headers(&headers)
-                                .filter(|d| *d < Duration::from_secs(60))
-                                .unwrap_or_else(|| {
-                                    Self::default_retry_delay(retries_remaining, max_retries)
-                                });
-                            tokio::time::sleep(delay).await;
-                            retries_remaining -= 1;
-                            continue;
-                        }
 
-                        return Err(Error::Http(http_err));
-                    }
-                    Err(err) => {
-                        let is_timeout = err.is_timeout();
-                        if retries_remaining > 0 {
-                            let delay = Self::default_retry_delay(retries_remaining, max_retries);
-                            tokio::time::sleep(delay).await;
-                            retries_remaining -= 1;
-                            continue;
-                        }
-
-                        if is_timeout {
-                            return Err(Error::Timeout);
-                        }
-                        return Err(Error::Transport(err));
-                    }
-                },
+                    let err = Error::Http(http_err);
+                    span.failed(&err);
+                    return Err(err);
+                }
+                Ok(Err(err)) => err,
+            };
+
+            let delay = policy.delay(retry_count, None);
+            if retries_remaining > 0 && policy.should_retry_error(&err) && within_deadline(delay) {
+                span.retry(retry_count + 1, delay, &err);
+                tokio::time::sleep(delay).await;
+                retries_remaining -= 1;
+                last_err = Some(err);
+                continue;
             }
+
+            let err = match err {
+                Error::Transport(e) if e.is_timeout() => Error::Timeout,
+                other => other,
+            };
+            span.failed(&err);
+            return Err(err);
         }
     }
 
@@ -553,22 +746,22 @@ impl Inner {
         T: DeserializeOwned + Send + 'static,
         B: Serialize + ?Sized,
     {
-        let response = self
-            .request_raw(method, path_or_url, query, body, options)
+        let (response, span, idempotency_key) = self
+            .send_json(method, path_or_url, query, body, options)
             .await?;
 
-        let headers = response.headers().clone();
-        let request_id = headers
-            .get(HEADER_REQUEST_ID)
-            .and_then(|v| v.to_str().ok())
-            .map(|s| s.to_string());
+        let headers = response.headers.clone();
+        let request_id = request_id(&headers);
+        let span = Arc::new(span);
+        span.stream_started();
 
         let cancel = tokio_util::sync::CancellationToken::new();
         let cancel_for_stream = cancel.clone();
-        let bytes_stream: BoxStream<'static, Result<Bytes, reqwest::Error>> =
-            Box::pin(response.bytes_stream());
+        let bytes_stream = response.body;
 
         let headers_for_errors = Arc::new(headers.clone());
+        let idempotency_key = Arc::new(idempotency_key);
+        let span_for_stream = span.clone();
 
         let stream = futures_util::stream::unfold(
             (
@@ -580,6 +773,8 @@ impl Inner {
             ),
             move |(mut bytes_stream, mut parser, mut pending, mut done, cancel)| {
                 let headers_for_errors = headers_for_errors.clone();
+                let idempotency_key = idempotency_key.clone();
+                let span = span_for_stream.clone();
                 async move {
                     if done {
                         return None;
@@ -587,6 +782,7 @@ impl Inner {
 
                     loop {
                         if cancel.is_cancelled() {
+                            span.stream_aborted();
                             return None;
                         }
 
@@ -597,13 +793,14 @@ impl Inner {
                                 Some("error") => {
                                     let json = serde_json::from_str::<Value>(&ev.data).ok();
                                     let message = extract_error_message(json.as_ref(), &ev.data);
-                                    let api_err = ApiError::new(
+                                    let mut api_err = ApiError::new(
                                         None,
                                         Some(headers_for_errors.as_ref()),
                                         json,
                                         message,
                                     );
-                                    let err = Error::Http(HttpApiError::Other(api_err));
+                                    api_err.idempotency_key = (*idempotency_key).clone();
+                                    let err = Error::Http(HttpApiError::from_status(None, api_err));
                                     done = true;
                                     return Some((
                                         Err(err),
@@ -611,6 +808,7 @@ impl Inner {
                                     ));
                                 }
                                 Some(name) if allowed.contains(&name) => {
+                                    span.stream_event(name, &ev.data);
                                     let item = match serde_json::from_str::<T>(&ev.data) {
                                         Ok(v) => v,
                                         Err(e) => {
@@ -631,7 +829,10 @@ impl Inner {
                         }
 
                         let next = tokio::select! {
-                          _ = cancel.cancelled() => return None,
+                          _ = cancel.cancelled() => {
+                              span.stream_aborted();
+                              return None;
+                          }
                           next = bytes_stream.next() => next,
                         };
 
@@ -649,7 +850,7 @@ impl Inner {
                             Some(Err(e)) => {
                                 done = true;
                                 return Some((
-                                    Err(Error::Transport(e)),
+                                    Err(e),
                                     (bytes_stream, parser, pending, done, cancel),
                                 ));
                             }
@@ -658,7 +859,10 @@ impl Inner {
                                     pending.push_back(ev);
                                     continue;
                                 }
-                                Ok(None) => return None,
+                                Ok(None) => {
+                                    span.stream_finished();
+                                    return None;
+                                }
                                 Err(e) => {
                                     done = true;
                                     return Some((
@@ -671,9 +875,66 @@ impl Inner {
                     }
                 }
             },
-        );
+        )
+        .inspect(move |item| {
+            if let Err(err) = item {
+                span.stream_error(err);
+            }
+        });
+
+        Ok(RawStream::new(
+            Box::pin(stream),
+            cancel,
+            request_id,
+            RateLimitInfo::from_headers(&headers),
+        ))
+    }
+}
+
+pub(crate) fn request_id(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(HEADER_REQUEST_ID)
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string())
+}
+
+// An explicit key (option or header) always wins; otherwise every non-GET call gets a fresh key
+// that is reused for all of its retries, so the server can tell a retry from a new request.
+fn idempotency_key(method: &Method, options: &RequestOptions) -> Option<String> {
+    if let Some(key) = &options.idempotency_key {
+        return Some(key.clone());
+    }
+    if let Some(key) = options.headers.get(HEADER_IDEMPOTENCY_KEY) {
+        return key.to_str().ok().map(str::to_string);
+    }
+    if options.remove_headers.contains(&HEADER_IDEMPOTENCY_KEY)
+        || matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+    {
+        return None;
+    }
+    Some(format!(
+        "anthropic-sdk-rust-retry-{:016x}{:016x}",
+        fastrand::u64(..),
+        fastrand::u64(..)
+    ))
+}
+
+fn http_client(options: &ClientOptions) -> Result<Option<HttpClient>, Error> {
+    let configured = [
+        options.transport.is_some(),
+        options.http_client.is_some(),
+        options.http.is_some(),
+    ];
+    if configured.iter().filter(|set| **set).count() > 1 {
+        return Err(Error::Internal(
+            "only one of transport, http_client and http may be set".to_string(),
+        ));
+    }
 
-        Ok(RawStream::new(Box::pin(stream), cancel, request_id))
+    match (&options.http_client, &options.http) {
+        (Some(http), _) => Ok(Some(http.clone())),
+        (None, Some(config)) => Ok(Some(config.build_client()?)),
+        (None, None) => Ok(None),
     }
 }
 
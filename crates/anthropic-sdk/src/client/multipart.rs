@@ -0,0 +1,174 @@
+use crate::client::transport::HttpBody;
+use bytes::Bytes;
+use futures_util::{StreamExt, TryStreamExt};
+use reqwest::header::HeaderValue;
+
+const PROGRESS_CHUNK_LEN: usize = 64 * 1024;
+
+pub(crate) struct MultipartForm {
+    boundary: String,
+    parts: Vec<Part>,
+    on_progress: Option<Box<dyn FnMut(u64) + Send>>,
+}
+
+pub(crate) struct Part {
+    name: String,
+    filename: Option<String>,
+    content_type: Option<String>,
+    body: HttpBody,
+    len: Option<u64>,
+}
+
+impl Part {
+    pub fn new(name: impl Into<String>, body: HttpBody) -> Self {
+        let len = match &body {
+            HttpBody::Bytes(bytes) => Some(bytes.len() as u64),
+            HttpBody::Stream(_) => None,
+        };
+        Self {
+            name: name.into(),
+            filename: None,
+            content_type: None,
+            body,
+            len,
+        }
+    }
+
+    // Declares the size of a streamed body so the form can send a Content-Length.
+    pub fn content_length(mut self, len: u64) -> Self {
+        self.len = Some(len);
+        self
+    }
+
+    pub fn file_name(mut self, filename: impl Into<String>) -> Self {
+        self.filename = Some(filename.into());
+        self
+    }
+
+    pub fn content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.content_type = Some(content_type.into());
+        self
+    }
+
+    fn header(&self, boundary: &str) -> String {
+        let mut header = format!(
+            "--{boundary}\r\nContent-Disposition: form-data; name=\"{}\"",
+            escape_quoted(&self.name)
+        );
+        if let Some(filename) = &self.filename {
+            header.push_str(&format!("; filename=\"{}\"", escape_quoted(filename)));
+        }
+        header.push_str("\r\n");
+        if let Some(content_type) = &self.content_type {
+            header.push_str(&format!("Content-Type: {content_type}\r\n"));
+        }
+        header.push_str("\r\n");
+        header
+    }
+}
+
+impl MultipartForm {
+    pub fn new() -> Self {
+        Self {
+            boundary: format!("{:016x}{:016x}", fastrand::u64(..), fastrand::u64(..)),
+            parts: Vec::new(),
+            on_progress: None,
+        }
+    }
+
+    pub fn part(mut self, part: Part) -> Self {
+        self.parts.push(part);
+        self
+    }
+
+    pub fn content_type(&self) -> HeaderValue {
+        HeaderValue::from_str(&format!("multipart/form-data; boundary={}", self.boundary))
+            .expect("boundary is always a valid header value")
+    }
+
+    // Known only when every part has a known size.
+    pub fn content_length(&self) -> Option<u64> {
+        let closing = format!("--{}--\r\n", self.boundary).len() as u64;
+        self.parts.iter().try_fold(closing, |total, part| {
+            Some(total + part.header(&self.boundary).len() as u64 + part.len? + 2)
+        })
+    }
+
+    // Called with the number of body bytes handed to the transport so far.
+    pub fn on_progress(mut self, callback: impl FnMut(u64) + Send + 'static) -> Self {
+        self.on_progress = Some(Box::new(callback));
+        self
+    }
+
+    pub fn into_body(mut self) -> HttpBody {
+        let Some(mut callback) = self.on_progress.take() else {
+            return self.encode();
+        };
+        let stream = match self.encode() {
+            HttpBody::Bytes(bytes) => {
+                let chunks: Vec<_> = (0..bytes.len())
+                    .step_by(PROGRESS_CHUNK_LEN)
+                    .map(
+                        |start| Ok(bytes.slice(start..bytes.len().min(start + PROGRESS_CHUNK_LEN))),
+                    )
+                    .collect();
+                futures_util::stream::iter(chunks).boxed()
+            }
+            HttpBody::Stream(stream) => stream,
+        };
+        let mut sent = 0;
+        HttpBody::Stream(
+            stream
+                .inspect_ok(move |chunk| {
+                    sent += chunk.len() as u64;
+                    callback(sent);
+                })
+                .boxed(),
+        )
+    }
+
+    fn encode(self) -> HttpBody {
+        let closing = Bytes::from(format!("--{}--\r\n", self.boundary));
+        if self
+            .parts
+            .iter()
+            .all(|p| matches!(p.body, HttpBody::Bytes(_)))
+        {
+            let mut out = Vec::new();
+            for part in self.parts {
+                out.extend_from_slice(part.header(&self.boundary).as_bytes());
+                if let HttpBody::Bytes(bytes) = part.body {
+                    out.extend_from_slice(&bytes);
+                }
+                out.extend_from_slice(b"\r\n");
+            }
+            out.extend_from_slice(&closing);
+            return HttpBody::Bytes(out.into());
+        }
+
+        let mut streams = Vec::new();
+        for part in self.parts {
+            let header = Bytes::from(part.header(&self.boundary));
+            streams.push(futures_util::stream::once(async move { Ok(header) }).boxed());
+            streams.push(match part.body {
+                HttpBody::Bytes(bytes) => {
+                    futures_util::stream::once(async move { Ok(bytes) }).boxed()
+                }
+                HttpBody::Stream(stream) => stream,
+            });
+            streams.push(
+                futures_util::stream::once(async { Ok(Bytes::from_static(b"\r\n")) }).boxed(),
+            );
+        }
+        streams.push(futures_util::stream::once(async move { Ok(closing) }).boxed());
+        HttpBody::Stream(futures_util::stream::iter(streams).flatten().boxed())
+    }
+}
+
+fn escape_quoted(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
@@ -0,0 +1,228 @@
+use crate::client::retry::retry_after;
+use crate::client::HttpRequest;
+use crate::time::parse_rfc3339;
+use futures_util::future::BoxFuture;
+use reqwest::header::HeaderMap;
+use reqwest::{Method, StatusCode};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RateLimitBucket {
+    pub limit: Option<u64>,
+    pub remaining: Option<u64>,
+    pub reset: Option<SystemTime>,
+}
+
+impl RateLimitBucket {
+    fn from_headers(headers: &HeaderMap, prefix: &str) -> Self {
+        let get = |suffix: &str| {
+            headers
+                .get(format!("anthropic-ratelimit-{prefix}-{suffix}"))
+                .and_then(|v| v.to_str().ok())
+        };
+        Self {
+            limit: get("limit").and_then(|v| v.parse().ok()),
+            remaining: get("remaining").and_then(|v| v.parse().ok()),
+            reset: get("reset").and_then(parse_rfc3339),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.limit.is_none() && self.remaining.is_none() && self.reset.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RateLimitInfo {
+    pub requests: RateLimitBucket,
+    pub input_tokens: RateLimitBucket,
+    pub output_tokens: RateLimitBucket,
+    pub tokens: RateLimitBucket,
+    pub retry_after: Option<Duration>,
+}
+
+impl RateLimitInfo {
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        Self {
+            requests: RateLimitBucket::from_headers(headers, "requests"),
+            input_tokens: RateLimitBucket::from_headers(headers, "input-tokens"),
+            output_tokens: RateLimitBucket::from_headers(headers, "output-tokens"),
+            tokens: RateLimitBucket::from_headers(headers, "tokens"),
+            retry_after: retry_after(headers),
+        }
+    }
+}
+
+type Estimator = Arc<dyn Fn(&HttpRequest) -> BoxFuture<'static, u64> + Send + Sync>;
+
+// Tracks the budget reported by `anthropic-ratelimit-*` headers and holds requests back until
+// the relevant window resets, instead of letting them fail with 429.
+pub struct RateLimiter {
+    state: Mutex<State>,
+    estimator: Option<Estimator>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    requests: Budget,
+    input_tokens: Budget,
+    output_tokens: Budget,
+    blocked_until: Option<Instant>,
+}
+
+#[derive(Debug, Default)]
+struct Budget {
+    limit: Option<u64>,
+    remaining: Option<u64>,
+    reset: Option<Instant>,
+}
+
+impl Budget {
+    fn refresh(&mut self, now: Instant) {
+        if self.reset.is_some_and(|reset| reset <= now) {
+            self.remaining = self.limit;
+            self.reset = None;
+        }
+    }
+
+    fn wait(&self, now: Instant, needed: u64) -> Option<Duration> {
+        let remaining = self.remaining?;
+        // A request larger than the whole window can only ever run against a full budget.
+        let needed = needed.min(self.limit.unwrap_or(u64::MAX));
+        if remaining >= needed {
+            return None;
+        }
+        self.reset.map(|reset| reset.saturating_duration_since(now))
+    }
+
+    fn take(&mut self, amount: u64) {
+        if let Some(remaining) = self.remaining.as_mut() {
+            *remaining = remaining.saturating_sub(amount);
+        }
+    }
+
+    fn update(&mut self, bucket: &RateLimitBucket, now: Instant) {
+        if bucket.is_empty() {
+            return;
+        }
+        self.limit = bucket.limit.or(self.limit);
+        self.remaining = bucket.remaining;
+        self.reset = bucket
+            .reset
+            .map(|reset| now + reset.duration_since(SystemTime::now()).unwrap_or_default());
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for RateLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateLimiter")
+            .field("state", &self.state)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(State::default()),
+            estimator: None,
+        }
+    }
+
+    // Replaces the default byte-length heuristic, e.g. with a call to `messages.count_tokens`.
+    // Only message-creation requests are estimated, so that call does not recurse into itself.
+    pub fn input_token_estimator<F>(mut self, estimator: F) -> Self
+    where
+        F: Fn(&HttpRequest) -> BoxFuture<'static, u64> + Send + Sync + 'static,
+    {
+        self.estimator = Some(Arc::new(estimator));
+        self
+    }
+
+    pub(crate) fn estimate_input_tokens(&self, request: &HttpRequest) -> BoxFuture<'static, u64> {
+        if !spends_input_tokens(request) {
+            return Box::pin(async { 0 });
+        }
+        match &self.estimator {
+            Some(estimator) => estimator(request),
+            None => {
+                let estimate = estimate_input_tokens(request);
+                Box::pin(async move { estimate })
+            }
+        }
+    }
+
+    pub(crate) async fn acquire(&self, input_tokens: u64) {
+        loop {
+            let wait = {
+                let mut state = self.state.lock().unwrap();
+                let now = Instant::now();
+                state.requests.refresh(now);
+                state.input_tokens.refresh(now);
+                state.output_tokens.refresh(now);
+
+                let wait = [
+                    state
+                        .blocked_until
+                        .map(|until| until.saturating_duration_since(now))
+                        .filter(|wait| !wait.is_zero()),
+                    state.requests.wait(now, 1),
+                    state.input_tokens.wait(now, input_tokens),
+                    state.output_tokens.wait(now, 1),
+                ]
+                .into_iter()
+                .flatten()
+                .max();
+
+                match wait {
+                    Some(wait) => wait,
+                    None => {
+                        state.requests.take(1);
+                        state.input_tokens.take(input_tokens);
+                        return;
+                    }
+                }
+            };
+
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    pub(crate) fn observe(&self, status: StatusCode, headers: &HeaderMap) {
+        let info = RateLimitInfo::from_headers(headers);
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        state.requests.update(&info.requests, now);
+        state.input_tokens.update(&info.input_tokens, now);
+        state.output_tokens.update(&info.output_tokens, now);
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            if let Some(retry_after) = info.retry_after {
+                state.blocked_until = Some(now + retry_after);
+            }
+        }
+    }
+}
+
+fn spends_input_tokens(request: &HttpRequest) -> bool {
+    let path = request.url.path();
+    request.method == Method::POST
+        && (path.ends_with("/v1/messages") || path.ends_with("/v1/complete"))
+}
+
+fn estimate_input_tokens(request: &HttpRequest) -> u64 {
+    // Roughly four bytes of JSON per token; close enough to avoid most rejections.
+    request
+        .body
+        .as_ref()
+        .and_then(|body| body.as_bytes())
+        .map(|bytes| (bytes.len() as u64).div_ceil(4))
+        .unwrap_or(0)
+}
@@ -0,0 +1,171 @@
+use crate::error::Error;
+use httpdate::parse_http_date;
+use reqwest::header::{HeaderMap, HeaderName};
+use reqwest::StatusCode;
+use std::time::{Duration, SystemTime};
+
+const HEADER_RETRY_AFTER: HeaderName = HeaderName::from_static("retry-after");
+const HEADER_RETRY_AFTER_MS: HeaderName = HeaderName::from_static("retry-after-ms");
+const HEADER_X_SHOULD_RETRY: HeaderName = HeaderName::from_static("x-should-retry");
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub multiplier: f64,
+    // Fraction of each backoff delay that may be randomly subtracted, between 0.0 and 1.0.
+    pub jitter: f64,
+    pub retry_statuses: Vec<StatusCode>,
+    pub retry_server_errors: bool,
+    pub retry_timeouts: bool,
+    pub retry_connection_errors: bool,
+    // `retry-after` values above this fall back to the backoff curve; `None` always honors them.
+    pub max_retry_after: Option<Duration>,
+    pub deadline: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(8),
+            multiplier: 2.0,
+            jitter: 0.25,
+            retry_statuses: vec![
+                StatusCode::REQUEST_TIMEOUT,
+                StatusCode::CONFLICT,
+                StatusCode::TOO_MANY_REQUESTS,
+            ],
+            retry_server_errors: true,
+            retry_timeouts: true,
+            retry_connection_errors: true,
+            max_retry_after: Some(Duration::from_secs(60)),
+            deadline: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn never() -> Self {
+        Self {
+            retry_statuses: Vec::new(),
+            retry_server_errors: false,
+            retry_timeouts: false,
+            retry_connection_errors: false,
+            ..Self::default()
+        }
+    }
+
+    pub fn initial_delay(mut self, delay: Duration) -> Self {
+        self.initial_delay = delay;
+        self
+    }
+
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn retry_statuses(mut self, statuses: impl IntoIterator<Item = StatusCode>) -> Self {
+        self.retry_statuses = statuses.into_iter().collect();
+        self
+    }
+
+    pub fn retry_server_errors(mut self, retry: bool) -> Self {
+        self.retry_server_errors = retry;
+        self
+    }
+
+    pub fn retry_timeouts(mut self, retry: bool) -> Self {
+        self.retry_timeouts = retry;
+        self
+    }
+
+    pub fn retry_connection_errors(mut self, retry: bool) -> Self {
+        self.retry_connection_errors = retry;
+        self
+    }
+
+    pub fn max_retry_after(mut self, max: Option<Duration>) -> Self {
+        self.max_retry_after = max;
+        self
+    }
+
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub fn should_retry_status(&self, status: StatusCode, headers: &HeaderMap) -> bool {
+        // The policy has the final say; the server can only veto a retry it would make.
+        let allowed = self.retry_statuses.contains(&status)
+            || (self.retry_server_errors && status.is_server_error());
+        allowed
+            && headers
+                .get(HEADER_X_SHOULD_RETRY)
+                .and_then(|v| v.to_str().ok())
+                != Some("false")
+    }
+
+    pub fn should_retry_error(&self, err: &Error) -> bool {
+        match err {
+            Error::Timeout => self.retry_timeouts,
+            Error::Transport(e) if e.is_timeout() => self.retry_timeouts,
+            Error::Transport(_) | Error::Connection(_) => self.retry_connection_errors,
+            _ => false,
+        }
+    }
+
+    pub fn backoff(&self, retry_count: u32) -> Duration {
+        let initial = self.initial_delay.as_secs_f64();
+        let max_delay = self.max_delay.as_secs_f64();
+
+        let sleep_seconds = (initial * self.multiplier.powi(retry_count as i32)).min(max_delay);
+        let jitter = 1.0 - fastrand::f64() * self.jitter.clamp(0.0, 1.0);
+        Duration::from_secs_f64((sleep_seconds * jitter).max(0.0))
+    }
+
+    pub fn delay(&self, retry_count: u32, headers: Option<&HeaderMap>) -> Duration {
+        headers
+            .and_then(retry_after)
+            .filter(|d| self.max_retry_after.is_none_or(|max| *d < max))
+            .unwrap_or_else(|| self.backoff(retry_count))
+    }
+}
+
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    if let Some(ms) = headers
+        .get(HEADER_RETRY_AFTER_MS)
+        .and_then(|v| v.to_str().ok())
+    {
+        if let Ok(ms) = ms.parse::<f64>() {
+            if ms.is_finite() && ms >= 0.0 {
+                return Some(Duration::from_millis(ms as u64));
+            }
+        }
+    }
+
+    let ra = headers.get(HEADER_RETRY_AFTER)?.to_str().ok()?;
+    if let Ok(seconds) = ra.parse::<f64>() {
+        if seconds.is_finite() && seconds >= 0.0 {
+            return Some(Duration::from_millis((seconds * 1000.0) as u64));
+        }
+        return None;
+    }
+
+    // HTTP-date
+    let at = parse_http_date(ra).ok()?;
+    let now = SystemTime::now();
+    let wait = at.duration_since(now).ok()?;
+    Some(wait)
+}
@@ -0,0 +1,210 @@
+#[cfg(feature = "otel")]
+use crate::client::gen_ai::Operation;
+use crate::client::HttpRequest;
+use crate::error::Error;
+use reqwest::header::HeaderMap;
+use reqwest::{Method, StatusCode, Url};
+use std::fmt;
+use std::time::Duration;
+#[cfg(any(feature = "tracing", feature = "otel"))]
+use std::time::Instant;
+
+// One logical API call, across all of its retries and, for streams, until the stream ends.
+// Without any telemetry feature enabled every method compiles down to nothing.
+pub(crate) struct RequestSpan {
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+    #[cfg(any(feature = "tracing", feature = "otel"))]
+    started: Instant,
+    #[cfg(feature = "otel")]
+    operation: std::sync::Mutex<Option<Operation>>,
+}
+
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+impl RequestSpan {
+    pub fn new(method: &Method, url: &Url) -> Self {
+        Self {
+            #[cfg(feature = "tracing")]
+            span: tracing::info_span!(
+                "anthropic.request",
+                method = %method,
+                path = url.path(),
+                model = tracing::field::Empty,
+                retry_count = 0u32,
+                status = tracing::field::Empty,
+                request_id = tracing::field::Empty,
+                latency_ms = tracing::field::Empty,
+                input_tokens = tracing::field::Empty,
+                output_tokens = tracing::field::Empty,
+            ),
+            #[cfg(any(feature = "tracing", feature = "otel"))]
+            started: Instant::now(),
+            #[cfg(feature = "otel")]
+            operation: std::sync::Mutex::new(None),
+        }
+    }
+
+    pub fn attempt(&self, request: &HttpRequest, retry_count: u32) {
+        #[cfg(feature = "otel")]
+        if retry_count == 0 {
+            *self.operation.lock().unwrap() = Operation::start(request);
+        }
+        #[cfg(feature = "tracing")]
+        {
+            if retry_count == 0 {
+                if let Some(model) = request_model(request) {
+                    self.span.record("model", model.as_str());
+                }
+            }
+            self.span.record("retry_count", retry_count);
+            tracing::debug!(
+                parent: &self.span,
+                method = %request.method,
+                url = %request.url,
+                headers = ?RedactedHeaders(&request.headers),
+                "sending request"
+            );
+        }
+    }
+
+    pub fn response(&self, status: StatusCode, headers: &HeaderMap) {
+        #[cfg(feature = "tracing")]
+        {
+            self.span.record("status", status.as_u16());
+            if let Some(request_id) = crate::client::request_id(headers) {
+                self.span.record("request_id", request_id.as_str());
+            }
+            self.span
+                .record("latency_ms", self.started.elapsed().as_millis() as u64);
+        }
+    }
+
+    pub fn retry(&self, retry_count: u32, delay: Duration, reason: &dyn fmt::Display) {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(
+            parent: &self.span,
+            retry_count,
+            delay_ms = delay.as_millis() as u64,
+            reason = %reason,
+            "retrying request"
+        );
+    }
+
+    pub fn failed(&self, err: &Error) {
+        #[cfg(feature = "otel")]
+        if let Some(operation) = self.operation.lock().unwrap().as_mut() {
+            operation.fail(err);
+        }
+        #[cfg(feature = "tracing")]
+        tracing::debug!(parent: &self.span, error = %err, "request failed");
+    }
+
+    // Accepts a message body or a `message_start`/`message_delta` stream event.
+    pub fn observe(&self, body: &[u8]) {
+        #[cfg(any(feature = "tracing", feature = "otel"))]
+        {
+            let Ok(value) = serde_json::from_slice::<serde_json::Value>(body) else {
+                return;
+            };
+            #[cfg(feature = "otel")]
+            if let Some(operation) = self.operation.lock().unwrap().as_mut() {
+                operation.observe(&value);
+            }
+            #[cfg(feature = "tracing")]
+            if let Some(usage) = value
+                .get("usage")
+                .or_else(|| value.get("message").and_then(|m| m.get("usage")))
+            {
+                if let Some(tokens) = usage.get("input_tokens").and_then(|v| v.as_u64()) {
+                    self.span.record("input_tokens", tokens);
+                }
+                if let Some(tokens) = usage.get("output_tokens").and_then(|v| v.as_u64()) {
+                    self.span.record("output_tokens", tokens);
+                }
+            }
+        }
+    }
+
+    pub fn stream_started(&self) {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(parent: &self.span, "stream started");
+    }
+
+    pub fn stream_event(&self, event: &str, data: &str) {
+        // `message_start` carries no generated tokens, so the first chunk is the first delta.
+        #[cfg(feature = "otel")]
+        if event == "content_block_delta" {
+            if let Some(operation) = self.operation.lock().unwrap().as_mut() {
+                operation.first_chunk(self.started.elapsed());
+            }
+        }
+        if matches!(event, "message_start" | "message_delta") {
+            self.observe(data.as_bytes());
+        }
+        #[cfg(feature = "tracing")]
+        tracing::trace!(parent: &self.span, event, "stream event");
+    }
+
+    pub fn stream_error(&self, err: &Error) {
+        self.failed(err);
+        #[cfg(feature = "tracing")]
+        tracing::warn!(parent: &self.span, error = %err, "stream failed");
+    }
+
+    pub fn stream_aborted(&self) {
+        self.failed(&Error::Aborted);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(parent: &self.span, "stream aborted");
+    }
+
+    pub fn stream_finished(&self) {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            parent: &self.span,
+            elapsed_ms = self.started.elapsed().as_millis() as u64,
+            "stream finished"
+        );
+        self.finish();
+    }
+
+    pub fn finish(&self) {
+        #[cfg(feature = "otel")]
+        if let Some(operation) = self.operation.lock().unwrap().take() {
+            operation.end(self.started.elapsed());
+        }
+    }
+}
+
+impl Drop for RequestSpan {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+#[cfg(feature = "tracing")]
+fn request_model(request: &HttpRequest) -> Option<String> {
+    let bytes = request.body.as_ref()?.as_bytes()?;
+    let body = serde_json::from_slice::<serde_json::Value>(bytes).ok()?;
+    body.get("model")?.as_str().map(str::to_string)
+}
+
+#[cfg(feature = "tracing")]
+struct RedactedHeaders<'a>(&'a HeaderMap);
+
+#[cfg(feature = "tracing")]
+impl fmt::Debug for RedactedHeaders<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        for (name, value) in self.0 {
+            if matches!(
+                name.as_str(),
+                "x-api-key" | "authorization" | "proxy-authorization"
+            ) {
+                map.entry(&name, &"[redacted]");
+            } else {
+                map.entry(&name, &value);
+            }
+        }
+        map.finish()
+    }
+}
@@ -0,0 +1,116 @@
+use crate::error::Error;
+use bytes::{Bytes, BytesMut};
+use futures_util::future::BoxFuture;
+use futures_util::stream::BoxStream;
+use futures_util::{StreamExt, TryStreamExt};
+use reqwest::header::HeaderMap;
+use reqwest::{Client as HttpClient, Method, StatusCode, Url};
+use std::fmt;
+
+pub type BodyStream = BoxStream<'static, Result<Bytes, Error>>;
+
+pub enum HttpBody {
+    Bytes(Bytes),
+    Stream(BodyStream),
+}
+
+impl fmt::Debug for HttpBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bytes(bytes) => f.debug_tuple("Bytes").field(&bytes.len()).finish(),
+            Self::Stream(_) => f.write_str("Stream"),
+        }
+    }
+}
+
+impl HttpBody {
+    pub fn as_bytes(&self) -> Option<&Bytes> {
+        match self {
+            Self::Bytes(bytes) => Some(bytes),
+            Self::Stream(_) => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct HttpRequest {
+    pub method: Method,
+    pub url: Url,
+    pub headers: HeaderMap,
+    pub body: Option<HttpBody>,
+}
+
+pub struct HttpResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: BodyStream,
+}
+
+impl fmt::Debug for HttpResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpResponse")
+            .field("status", &self.status)
+            .field("headers", &self.headers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl HttpResponse {
+    pub fn new(status: StatusCode, headers: HeaderMap, body: impl Into<Bytes>) -> Self {
+        let body = body.into();
+        Self {
+            status,
+            headers,
+            body: futures_util::stream::once(async move { Ok(body) }).boxed(),
+        }
+    }
+
+    pub async fn bytes(self) -> Result<Bytes, Error> {
+        let mut out = BytesMut::new();
+        let mut body = self.body;
+        while let Some(chunk) = body.next().await {
+            out.extend_from_slice(&chunk?);
+        }
+        Ok(out.freeze())
+    }
+}
+
+pub trait HttpTransport: fmt::Debug + Send + Sync {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, Error>>;
+}
+
+#[derive(Debug, Clone)]
+pub struct ReqwestTransport {
+    client: HttpClient,
+}
+
+impl ReqwestTransport {
+    pub fn new(client: HttpClient) -> Self {
+        Self { client }
+    }
+}
+
+impl HttpTransport for ReqwestTransport {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, Error>> {
+        Box::pin(async move {
+            let mut req = self
+                .client
+                .request(request.method, request.url)
+                .headers(request.headers);
+            match request.body {
+                Some(HttpBody::Bytes(bytes)) => req = req.body(bytes),
+                Some(HttpBody::Stream(stream)) => {
+                    req = req.body(reqwest::Body::wrap_stream(stream))
+                }
+                None => {}
+            }
+
+            let resp = req.send().await?;
+            Ok(HttpResponse {
+                status: resp.status(),
+                headers: resp.headers().clone(),
+                body: resp.bytes_stream().map_err(Error::Transport).boxed(),
+            })
+        })
+    }
+}
@@ -1,13 +1,71 @@
+use crate::client::RateLimitInfo;
 use reqwest::header::HeaderMap;
 use reqwest::StatusCode;
 use serde_json::Value;
 use std::fmt;
 use thiserror::Error;
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    InvalidRequest,
+    Authentication,
+    Billing,
+    PermissionDenied,
+    NotFound,
+    RequestTooLarge,
+    RateLimit,
+    Api,
+    Overloaded,
+    Timeout,
+    Other(String),
+}
+
+impl ErrorKind {
+    pub fn from_type(kind: &str) -> Self {
+        match kind {
+            "invalid_request_error" => Self::InvalidRequest,
+            "authentication_error" => Self::Authentication,
+            "billing_error" => Self::Billing,
+            "permission_error" => Self::PermissionDenied,
+            "not_found_error" => Self::NotFound,
+            "request_too_large" => Self::RequestTooLarge,
+            "rate_limit_error" => Self::RateLimit,
+            "api_error" => Self::Api,
+            "overloaded_error" => Self::Overloaded,
+            "timeout_error" => Self::Timeout,
+            other => Self::Other(other.to_string()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::InvalidRequest => "invalid_request_error",
+            Self::Authentication => "authentication_error",
+            Self::Billing => "billing_error",
+            Self::PermissionDenied => "permission_error",
+            Self::NotFound => "not_found_error",
+            Self::RequestTooLarge => "request_too_large",
+            Self::RateLimit => "rate_limit_error",
+            Self::Api => "api_error",
+            Self::Overloaded => "overloaded_error",
+            Self::Timeout => "timeout_error",
+            Self::Other(other) => other,
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ApiError {
     pub status: Option<StatusCode>,
     pub request_id: Option<String>,
+    pub idempotency_key: Option<String>,
+    pub rate_limit: Box<RateLimitInfo>,
     pub message: Option<String>,
     pub body: Option<Value>,
 }
@@ -23,14 +81,26 @@ impl ApiError {
             .and_then(|h| h.get("request-id"))
             .and_then(|v| v.to_str().ok())
             .map(|s| s.to_string());
+        let rate_limit = Box::new(headers.map(RateLimitInfo::from_headers).unwrap_or_default());
 
         Self {
             status,
             request_id,
+            idempotency_key: None,
+            rate_limit,
             message,
             body,
         }
     }
+
+    pub fn kind(&self) -> Option<ErrorKind> {
+        self.body
+            .as_ref()
+            .and_then(|b| b.get("error"))
+            .and_then(|e| e.get("type"))
+            .and_then(|t| t.as_str())
+            .map(ErrorKind::from_type)
+    }
 }
 
 impl fmt::Display for ApiError {
@@ -48,27 +118,35 @@ impl fmt::Display for ApiError {
 #[derive(Debug, Error)]
 pub enum HttpApiError {
     #[error("400 Bad Request: {0}")]
-    BadRequest(ApiError),
+    BadRequest(Box<ApiError>),
     #[error("401 Authentication Error: {0}")]
-    Authentication(ApiError),
+    Authentication(Box<ApiError>),
     #[error("403 Permission Denied: {0}")]
-    PermissionDenied(ApiError),
+    PermissionDenied(Box<ApiError>),
     #[error("404 Not Found: {0}")]
-    NotFound(ApiError),
+    NotFound(Box<ApiError>),
     #[error("409 Conflict: {0}")]
-    Conflict(ApiError),
+    Conflict(Box<ApiError>),
     #[error("422 Unprocessable Entity: {0}")]
-    UnprocessableEntity(ApiError),
+    UnprocessableEntity(Box<ApiError>),
     #[error("429 Rate Limit: {0}")]
-    RateLimit(ApiError),
+    RateLimit(Box<ApiError>),
+    #[error("529 Overloaded: {0}")]
+    Overloaded(Box<ApiError>),
     #[error("5xx Internal Server Error: {0}")]
-    InternalServer(ApiError),
+    InternalServer(Box<ApiError>),
     #[error("API Error: {0}")]
-    Other(ApiError),
+    Other(Box<ApiError>),
 }
 
 impl HttpApiError {
     pub fn from_status(status: Option<StatusCode>, err: ApiError) -> Self {
+        let kind = err.kind();
+        // Boxed so that `Error` stays small enough to return by value.
+        let err = Box::new(err);
+        if kind == Some(ErrorKind::Overloaded) {
+            return Self::Overloaded(err);
+        }
         match status.map(|s| s.as_u16()) {
             Some(400) => Self::BadRequest(err),
             Some(401) => Self::Authentication(err),
@@ -77,10 +155,40 @@ impl HttpApiError {
             Some(409) => Self::Conflict(err),
             Some(422) => Self::UnprocessableEntity(err),
             Some(429) => Self::RateLimit(err),
+            Some(529) => Self::Overloaded(err),
             Some(s) if s >= 500 => Self::InternalServer(err),
-            _ => Self::Other(err),
+            Some(_) => Self::Other(err),
+            // Errors delivered inside an event stream carry no status, only `error.type`.
+            None => match kind {
+                Some(ErrorKind::InvalidRequest) => Self::BadRequest(err),
+                Some(ErrorKind::Authentication) => Self::Authentication(err),
+                Some(ErrorKind::PermissionDenied) => Self::PermissionDenied(err),
+                Some(ErrorKind::NotFound) => Self::NotFound(err),
+                Some(ErrorKind::RateLimit) => Self::RateLimit(err),
+                Some(ErrorKind::Api) => Self::InternalServer(err),
+                _ => Self::Other(err),
+            },
         }
     }
+
+    pub fn api_error(&self) -> &ApiError {
+        match self {
+            Self::BadRequest(err)
+            | Self::Authentication(err)
+            | Self::PermissionDenied(err)
+            | Self::NotFound(err)
+            | Self::Conflict(err)
+            | Self::UnprocessableEntity(err)
+            | Self::RateLimit(err)
+            | Self::Overloaded(err)
+            | Self::InternalServer(err)
+            | Self::Other(err) => err,
+        }
+    }
+
+    pub fn kind(&self) -> Option<ErrorKind> {
+        self.api_error().kind()
+    }
 }
 
 #[derive(Debug, Error)]
@@ -102,6 +210,9 @@ pub enum Error {
     #[error(transparent)]
     Transport(#[from] reqwest::Error),
 
+    #[error("connection error: {0}")]
+    Connection(#[source] Box<dyn std::error::Error + Send + Sync>),
+
     #[error(transparent)]
     Json(#[from] serde_json::Error),
 
@@ -114,6 +225,25 @@ pub enum Error {
     #[error("invalid jsonl stream: {0}")]
     InvalidJsonl(String),
 
+    #[error("invalid event stream: {0}")]
+    InvalidEventStream(String),
+
+    #[error("tool '{name}' input does not match its schema: {source}")]
+    ToolInput {
+        name: String,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    #[error("tool runner stopped after {iterations} iterations with tool calls still pending")]
+    MaxIterations { iterations: usize },
+
+    #[error("credentials error: {0}")]
+    Credentials(String),
+
+    #[error("circuit breaker is open for '{key}'")]
+    CircuitOpen { key: String },
+
     #[error("stream aborted")]
     Aborted,
 
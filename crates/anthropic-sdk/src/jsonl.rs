@@ -1,21 +1,20 @@
+use crate::client::{HttpResponse, RateLimitInfo};
 use crate::error::Error;
 use crate::streaming::RawStream;
-use bytes::Bytes;
-use futures_util::stream::BoxStream;
 use futures_util::StreamExt;
 use serde::de::DeserializeOwned;
 use tokio_util::sync::CancellationToken;
 
 pub(crate) fn jsonl_stream_from_response<T>(
-    response: reqwest::Response,
+    response: HttpResponse,
     cancel: CancellationToken,
     request_id: Option<String>,
 ) -> RawStream<T>
 where
     T: DeserializeOwned + Send + 'static,
 {
-    let bytes_stream: BoxStream<'static, Result<Bytes, reqwest::Error>> =
-        Box::pin(response.bytes_stream());
+    let rate_limit = RateLimitInfo::from_headers(&response.headers);
+    let bytes_stream = response.body;
     let cancel_for_stream = cancel.clone();
 
     let stream = futures_util::stream::unfold(
@@ -48,9 +47,7 @@ where
 
                 match next {
                     Some(Ok(chunk)) => buf.extend_from_slice(&chunk),
-                    Some(Err(e)) => {
-                        return Some((Err(Error::Transport(e)), (bytes_stream, buf, cancel)))
-                    }
+                    Some(Err(e)) => return Some((Err(e), (bytes_stream, buf, cancel))),
                     None => {
                         if buf.is_empty() {
                             return None;
@@ -69,5 +66,5 @@ where
         },
     );
 
-    RawStream::new(Box::pin(stream), cancel, request_id)
+    RawStream::new(Box::pin(stream), cancel, request_id, rate_limit)
 }
@@ -1,10 +1,23 @@
+#[cfg(feature = "bedrock")]
+pub mod bedrock;
 mod client;
 mod error;
 mod jsonl;
+pub mod middleware;
 mod pagination;
 pub mod resources;
 pub mod streaming;
+mod time;
+pub mod tool_runner;
 pub mod types;
+#[cfg(feature = "vertex")]
+pub mod vertex;
 
-pub use crate::client::{Anthropic, ApiResponse, ClientOptions, RequestOptions};
-pub use crate::error::{ApiError, Error, HttpApiError};
+pub use crate::client::{
+    Anthropic, ApiResponse, BodyStream, CircuitBreaker, CircuitScope, CircuitState, ClientBuilder,
+    ClientOptions, CredentialProvider, Credentials, EnvCredentials, FileCredentials, HttpBody,
+    HttpConfig, HttpRequest, HttpResponse, HttpTransport, RateLimitBucket, RateLimitInfo,
+    RateLimiter, RefreshingCredentials, RequestOptions, ReqwestTransport, RetryPolicy,
+};
+pub use crate::error::{ApiError, Error, ErrorKind, HttpApiError};
+pub use crate::pagination::{Page, PageParams, PageStream};
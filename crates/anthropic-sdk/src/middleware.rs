@@ -0,0 +1,82 @@
+use crate::client::{HttpRequest, HttpResponse, HttpTransport};
+use crate::error::Error;
+use futures_util::future::BoxFuture;
+use std::fmt;
+use std::sync::Arc;
+
+pub trait Middleware: fmt::Debug + Send + Sync {
+    fn handle<'a>(
+        &'a self,
+        request: HttpRequest,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, Result<HttpResponse, Error>>;
+}
+
+// The remainder of the chain: middleware registered after the current one, then the transport.
+#[derive(Clone, Copy)]
+pub struct Next<'a> {
+    middleware: &'a [Arc<dyn Middleware>],
+    transport: &'a dyn HttpTransport,
+}
+
+impl<'a> Next<'a> {
+    pub(crate) fn new(
+        middleware: &'a [Arc<dyn Middleware>],
+        transport: &'a dyn HttpTransport,
+    ) -> Self {
+        Self {
+            middleware,
+            transport,
+        }
+    }
+
+    pub fn run(self, request: HttpRequest) -> BoxFuture<'a, Result<HttpResponse, Error>> {
+        match self.middleware.split_first() {
+            Some((current, rest)) => current.handle(request, Next::new(rest, self.transport)),
+            None => self.transport.send(request),
+        }
+    }
+}
+
+impl fmt::Debug for Next<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Next")
+            .field("middleware", &self.middleware)
+            .field("transport", &self.transport)
+            .finish()
+    }
+}
+
+pub fn from_fn<F>(f: F) -> FnMiddleware<F>
+where
+    F: for<'a> Fn(HttpRequest, Next<'a>) -> BoxFuture<'a, Result<HttpResponse, Error>>
+        + Send
+        + Sync,
+{
+    FnMiddleware { f }
+}
+
+pub struct FnMiddleware<F> {
+    f: F,
+}
+
+impl<F> fmt::Debug for FnMiddleware<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FnMiddleware").finish_non_exhaustive()
+    }
+}
+
+impl<F> Middleware for FnMiddleware<F>
+where
+    F: for<'a> Fn(HttpRequest, Next<'a>) -> BoxFuture<'a, Result<HttpResponse, Error>>
+        + Send
+        + Sync,
+{
+    fn handle<'a>(
+        &'a self,
+        request: HttpRequest,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, Result<HttpResponse, Error>> {
+        (self.f)(request, next)
+    }
+}
@@ -1,4 +1,15 @@
+use crate::client::{Anthropic, ApiResponse, Inner, RequestOptions};
+use crate::error::Error;
+use futures_core::Stream;
+use futures_util::stream::BoxStream;
+use futures_util::StreamExt;
+use reqwest::Method;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct PageParams {
@@ -12,6 +23,22 @@ pub struct PageParams {
     pub after_id: Option<String>,
 }
 
+impl PageParams {
+    fn query(&self) -> Vec<(String, String)> {
+        let mut query = Vec::new();
+        if let Some(limit) = self.limit {
+            query.push(("limit".to_string(), limit.to_string()));
+        }
+        if let Some(before_id) = &self.before_id {
+            query.push(("before_id".to_string(), before_id.clone()));
+        }
+        if let Some(after_id) = &self.after_id {
+            query.push(("after_id".to_string(), after_id.clone()));
+        }
+        query
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(bound(deserialize = "T: Deserialize<'de>"))]
 pub struct Page<T> {
@@ -23,6 +50,11 @@ pub struct Page<T> {
 
     pub first_id: Option<String>,
     pub last_id: Option<String>,
+
+    // The request that produced this page, so the following one can be fetched with the same
+    // path, headers and paging direction.
+    #[serde(skip)]
+    request: Option<PageRequest>,
 }
 
 impl<T> Page<T> {
@@ -57,4 +89,105 @@ impl<T> Page<T> {
             after_id: Some(last_id),
         })
     }
+
+    // Returns `None` once the last page has been reached, or for pages that were not returned by
+    // a list call.
+    pub async fn next_page(&self, client: &Anthropic) -> Result<Option<Page<T>>, Error>
+    where
+        T: DeserializeOwned,
+    {
+        match self.next_request() {
+            Some(request) => Ok(Some(request.send(&client.inner).await?.data)),
+            None => Ok(None),
+        }
+    }
+
+    fn next_request(&self) -> Option<PageRequest> {
+        let request = self.request.as_ref()?;
+        let params = self.next_params(&request.params)?;
+        Some(PageRequest {
+            params,
+            ..request.clone()
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct PageRequest {
+    path: String,
+    params: PageParams,
+    options: RequestOptions,
+}
+
+impl PageRequest {
+    pub fn new(path: impl Into<String>, params: PageParams, options: RequestOptions) -> Self {
+        Self {
+            path: path.into(),
+            params,
+            options,
+        }
+    }
+
+    pub async fn send<T>(self, inner: &Inner) -> Result<ApiResponse<Page<T>>, Error>
+    where
+        T: DeserializeOwned,
+    {
+        let mut response: ApiResponse<Page<T>> = inner
+            .request_json(
+                Method::GET,
+                &self.path,
+                Some(self.params.query()),
+                Option::<&()>::None,
+                self.options.clone(),
+            )
+            .await?;
+        response.data.request = Some(self);
+        Ok(response)
+    }
+}
+
+// Yields every item of every page, fetching the next page only once the current one is drained.
+pub struct PageStream<T> {
+    inner: BoxStream<'static, Result<T, Error>>,
+}
+
+impl<T> PageStream<T>
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    pub(crate) fn new(inner: Arc<Inner>, request: Result<PageRequest, Error>) -> Self {
+        let state = (Some(request), VecDeque::new());
+        let stream = futures_util::stream::unfold(state, move |(mut next, mut items)| {
+            let inner = inner.clone();
+            async move {
+                loop {
+                    if let Some(item) = items.pop_front() {
+                        return Some((Ok(item), (next, items)));
+                    }
+                    let page = match next.take()? {
+                        Ok(request) => request.send::<T>(&inner).await.map(|r| r.data),
+                        Err(err) => Err(err),
+                    };
+                    match page {
+                        Ok(page) => {
+                            next = page.next_request().map(Ok);
+                            items = page.data.into();
+                        }
+                        Err(err) => return Some((Err(err), (None, items))),
+                    }
+                }
+            }
+        });
+        Self {
+            inner: stream.boxed(),
+        }
+    }
+}
+
+impl<T> Stream for PageStream<T> {
+    type Item = Result<T, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().inner.as_mut().poll_next(cx)
+    }
 }
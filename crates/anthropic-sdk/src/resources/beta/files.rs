@@ -1,13 +1,17 @@
-use crate::client::{ApiResponse, Inner, RequestOptions};
+use crate::client::multipart::{MultipartForm, Part};
+use crate::client::{request_id, ApiResponse, HttpBody, Inner, RequestOptions};
 use crate::error::Error;
-use crate::pagination::Page;
-use crate::types::files::{DeletedFile, FileMetadata};
+use crate::pagination::{Page, PageParams, PageRequest, PageStream};
+use crate::types::files::{DeletedFile, FileListParams, FileMetadata};
 use bytes::Bytes;
+use futures_util::{StreamExt, TryStreamExt};
 use reqwest::header::{HeaderName, HeaderValue, ACCEPT};
-use reqwest::multipart::{Form, Part};
 use reqwest::Method;
-use std::path::PathBuf;
-use std::sync::Arc;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio_util::io::ReaderStream;
 
 const HEADER_ANTHROPIC_BETA: HeaderName = HeaderName::from_static("anthropic-beta");
 const BETA_FILES_API: &str = "files-api-2025-04-14";
@@ -22,23 +26,51 @@ impl Files {
         Self { inner }
     }
 
-    pub async fn list(&self, options: Option<RequestOptions>) -> Result<Page<FileMetadata>, Error> {
-        Ok(self.list_with_response(options).await?.data)
+    pub async fn list(
+        &self,
+        params: Option<FileListParams>,
+        options: Option<RequestOptions>,
+    ) -> Result<Page<FileMetadata>, Error> {
+        Ok(self.list_with_response(params, options).await?.data)
     }
 
     pub async fn list_with_response(
         &self,
+        params: Option<FileListParams>,
         options: Option<RequestOptions>,
     ) -> Result<ApiResponse<Page<FileMetadata>>, Error> {
+        Self::page_request(params, options)?.send(&self.inner).await
+    }
+
+    pub fn list_auto_paging(
+        &self,
+        params: Option<FileListParams>,
+        options: Option<RequestOptions>,
+    ) -> PageStream<FileMetadata> {
+        PageStream::new(self.inner.clone(), Self::page_request(params, options))
+    }
+
+    fn page_request(
+        params: Option<FileListParams>,
+        options: Option<RequestOptions>,
+    ) -> Result<PageRequest, Error> {
+        let params = params.unwrap_or_default();
         let mut options = options.unwrap_or_default();
+        let mut betas = params.betas.unwrap_or_default();
+        if !betas.iter().any(|b| b == BETA_FILES_API) {
+            betas.push(BETA_FILES_API.to_string());
+        }
         options.headers.insert(
             HEADER_ANTHROPIC_BETA,
-            HeaderValue::from_static(BETA_FILES_API),
+            HeaderValue::from_str(&betas.join(","))?,
         );
 
-        self.inner
-            .request_json(Method::GET, "/v1/files", None, Option::<&()>::None, options)
-            .await
+        let params = PageParams {
+            limit: params.limit,
+            before_id: params.before_id,
+            after_id: params.after_id,
+        };
+        Ok(PageRequest::new("/v1/files", params, options))
     }
 
     pub async fn delete(
@@ -126,6 +158,7 @@ impl Files {
             .headers
             .insert(ACCEPT, HeaderValue::from_static("application/binary"));
 
+        let idempotency_key = options.idempotency_key.clone();
         let resp = self
             .inner
             .request_raw(
@@ -136,18 +169,16 @@ impl Files {
                 options,
             )
             .await?;
-        let status = resp.status();
-        let headers = resp.headers().clone();
-        let request_id = headers
-            .get("request-id")
-            .and_then(|v| v.to_str().ok())
-            .map(|s| s.to_string());
+        let status = resp.status;
+        let headers = resp.headers.clone();
+        let request_id = request_id(&headers);
         let bytes = resp.bytes().await?;
         Ok(ApiResponse {
             data: bytes,
             request_id,
             status,
             headers,
+            idempotency_key,
         })
     }
 
@@ -174,31 +205,51 @@ impl Files {
             HeaderValue::from_str(&betas.join(","))?,
         );
 
-        let file_bytes = tokio::fs::read(&params.path).await.map_err(|e| {
-            Error::Internal(format!(
-                "failed to read file '{}': {e}",
-                params.path.display()
-            ))
-        })?;
-
         let filename = params.filename.clone().unwrap_or_else(|| {
-            params
-                .path
-                .file_name()
-                .unwrap_or_default()
-                .to_string_lossy()
-                .to_string()
+            match &params.file.kind {
+                SourceKind::Path(path) => path.file_name(),
+                _ => None,
+            }
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| "file".to_string())
         });
-        let mime_type = params.mime_type.clone();
+
+        let (source, head) = Source::open(params.file).await?;
+        if matches!(source, Source::Reader(_)) {
+            // A reader can only be consumed once, so there is nothing to resend on a retry.
+            options.max_retries = Some(0);
+        }
+        let mime_type = params.mime_type.or_else(|| {
+            mime_from_extension(&filename)
+                .or_else(|| mime_from_magic(&head))
+                .map(str::to_string)
+        });
+        if let Some(mime) = &mime_type {
+            HeaderValue::from_str(mime)?;
+        }
+        let on_progress = params.on_progress;
 
         let build_form = move || {
-            let mut part = Part::bytes(file_bytes.clone()).file_name(filename.clone());
+            let (body, len) = source.body()?;
+            let mut part = Part::new("file", body).file_name(filename.clone());
+            if let Some(len) = len {
+                part = part.content_length(len);
+            }
             if let Some(mime) = &mime_type {
-                part = part
-                    .mime_str(mime)
-                    .map_err(|e| Error::Internal(e.to_string()))?;
+                part = part.content_type(mime.clone());
+            }
+            let mut form = MultipartForm::new().part(part);
+            if let Some(callback) = &on_progress {
+                let callback = callback.clone();
+                let total_bytes = form.content_length();
+                form = form.on_progress(move |bytes_sent| {
+                    callback(UploadProgress {
+                        bytes_sent,
+                        total_bytes,
+                    })
+                });
             }
-            Ok(Form::new().part("file", part))
+            Ok(form)
         };
 
         self.inner
@@ -207,10 +258,267 @@ impl Files {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct FileUploadParams {
-    pub path: PathBuf,
+    pub file: UploadSource,
     pub filename: Option<String>,
     pub mime_type: Option<String>,
     pub betas: Option<Vec<String>>,
+    pub on_progress: Option<Arc<dyn Fn(UploadProgress) + Send + Sync>>,
+}
+
+impl FileUploadParams {
+    pub fn new(file: impl Into<UploadSource>) -> Self {
+        Self {
+            file: file.into(),
+            filename: None,
+            mime_type: None,
+            betas: None,
+            on_progress: None,
+        }
+    }
+
+    pub fn path(path: impl Into<PathBuf>) -> Self {
+        Self::new(UploadSource::path(path))
+    }
+
+    pub fn bytes(bytes: impl Into<Bytes>) -> Self {
+        Self::new(UploadSource::bytes(bytes))
+    }
+
+    pub fn reader(reader: impl AsyncRead + Send + 'static) -> Self {
+        Self::new(UploadSource::reader(reader))
+    }
+
+    pub fn filename(mut self, filename: impl Into<String>) -> Self {
+        self.filename = Some(filename.into());
+        self
+    }
+
+    pub fn mime_type(mut self, mime_type: impl Into<String>) -> Self {
+        self.mime_type = Some(mime_type.into());
+        self
+    }
+
+    pub fn betas(mut self, betas: Vec<String>) -> Self {
+        self.betas = Some(betas);
+        self
+    }
+
+    pub fn on_progress(
+        mut self,
+        callback: impl Fn(UploadProgress) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_progress = Some(Arc::new(callback));
+        self
+    }
+}
+
+impl fmt::Debug for FileUploadParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FileUploadParams")
+            .field("file", &self.file)
+            .field("filename", &self.filename)
+            .field("mime_type", &self.mime_type)
+            .field("betas", &self.betas)
+            .field("on_progress", &self.on_progress.is_some())
+            .finish()
+    }
+}
+
+// Reported after every chunk of the multipart request body is handed to the transport, so the
+// totals include the form framing around the file. Counting restarts from zero on a retry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UploadProgress {
+    pub bytes_sent: u64,
+    pub total_bytes: Option<u64>,
+}
+
+type BoxedReader = Box<dyn AsyncRead + Send + Unpin>;
+
+// Where the uploaded file comes from. Paths are streamed from disk on every attempt; readers are
+// streamed once and therefore disable retries.
+#[derive(Clone)]
+pub struct UploadSource {
+    kind: SourceKind,
+}
+
+#[derive(Clone)]
+enum SourceKind {
+    Path(PathBuf),
+    Bytes(Bytes),
+    Reader(Arc<Mutex<Option<BoxedReader>>>),
+}
+
+impl UploadSource {
+    pub fn path(path: impl Into<PathBuf>) -> Self {
+        Self {
+            kind: SourceKind::Path(path.into()),
+        }
+    }
+
+    pub fn bytes(bytes: impl Into<Bytes>) -> Self {
+        Self {
+            kind: SourceKind::Bytes(bytes.into()),
+        }
+    }
+
+    pub fn reader(reader: impl AsyncRead + Send + 'static) -> Self {
+        Self {
+            kind: SourceKind::Reader(Arc::new(Mutex::new(Some(Box::new(Box::pin(reader)))))),
+        }
+    }
+}
+
+impl fmt::Debug for UploadSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            SourceKind::Path(path) => f.debug_tuple("Path").field(path).finish(),
+            SourceKind::Bytes(bytes) => write!(f, "Bytes({} bytes)", bytes.len()),
+            SourceKind::Reader(_) => f.write_str("Reader"),
+        }
+    }
+}
+
+impl From<PathBuf> for UploadSource {
+    fn from(path: PathBuf) -> Self {
+        Self::path(path)
+    }
+}
+
+impl From<&Path> for UploadSource {
+    fn from(path: &Path) -> Self {
+        Self::path(path)
+    }
+}
+
+impl From<Bytes> for UploadSource {
+    fn from(bytes: Bytes) -> Self {
+        Self::bytes(bytes)
+    }
+}
+
+impl From<Vec<u8>> for UploadSource {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::bytes(bytes)
+    }
+}
+
+const SNIFF_LEN: usize = 16;
+const CHUNK_LEN: usize = 64 * 1024;
+
+enum Source {
+    Path { path: PathBuf, len: u64 },
+    Bytes(Bytes),
+    Reader(Mutex<Option<(Bytes, BoxedReader)>>),
+}
+
+impl Source {
+    // Also returns the first few bytes of the file for content sniffing.
+    async fn open(file: UploadSource) -> Result<(Self, Bytes), Error> {
+        match file.kind {
+            SourceKind::Path(path) => {
+                let mut file = tokio::fs::File::open(&path)
+                    .await
+                    .map_err(|e| read_error(&path, e))?;
+                let len = file
+                    .metadata()
+                    .await
+                    .map_err(|e| read_error(&path, e))?
+                    .len();
+                let head = read_head(&mut file)
+                    .await
+                    .map_err(|e| read_error(&path, e))?;
+                Ok((Self::Path { path, len }, head))
+            }
+            SourceKind::Bytes(bytes) => {
+                let head = bytes.slice(..bytes.len().min(SNIFF_LEN));
+                Ok((Self::Bytes(bytes), head))
+            }
+            SourceKind::Reader(reader) => {
+                let mut reader = reader.lock().unwrap().take().ok_or_else(|| {
+                    Error::Internal("upload reader has already been consumed".to_string())
+                })?;
+                let head = read_head(&mut reader)
+                    .await
+                    .map_err(|e| Error::Internal(format!("failed to read upload: {e}")))?;
+                Ok((Self::Reader(Mutex::new(Some((head.clone(), reader)))), head))
+            }
+        }
+    }
+
+    fn body(&self) -> Result<(HttpBody, Option<u64>), Error> {
+        match self {
+            Self::Path { path, len } => {
+                let path = path.clone();
+                let stream = futures_util::stream::once(async move {
+                    match tokio::fs::File::open(&path).await {
+                        Ok(file) => Ok(ReaderStream::with_capacity(file, CHUNK_LEN)
+                            .map_err(move |e| read_error(&path, e))),
+                        Err(e) => Err(read_error(&path, e)),
+                    }
+                })
+                .try_flatten();
+                Ok((HttpBody::Stream(stream.boxed()), Some(*len)))
+            }
+            Self::Bytes(bytes) => Ok((HttpBody::Bytes(bytes.clone()), Some(bytes.len() as u64))),
+            Self::Reader(reader) => {
+                let (head, reader) = reader.lock().unwrap().take().ok_or_else(|| {
+                    Error::Internal("an upload from a reader cannot be resent".to_string())
+                })?;
+                let stream = futures_util::stream::once(async move { Ok(head) }).chain(
+                    ReaderStream::with_capacity(reader, CHUNK_LEN)
+                        .map_err(|e| Error::Internal(format!("failed to read upload: {e}"))),
+                );
+                Ok((HttpBody::Stream(stream.boxed()), None))
+            }
+        }
+    }
+}
+
+async fn read_head<R: AsyncRead + Unpin>(reader: &mut R) -> std::io::Result<Bytes> {
+    let mut head = Vec::with_capacity(SNIFF_LEN);
+    reader.take(SNIFF_LEN as u64).read_to_end(&mut head).await?;
+    Ok(head.into())
+}
+
+fn read_error(path: &Path, e: std::io::Error) -> Error {
+    Error::Internal(format!("failed to read file '{}': {e}", path.display()))
+}
+
+fn mime_from_extension(filename: &str) -> Option<&'static str> {
+    let (_, extension) = filename.rsplit_once('.')?;
+    let mime = match extension.to_ascii_lowercase().as_str() {
+        "pdf" => "application/pdf",
+        "txt" => "text/plain",
+        "md" | "markdown" => "text/markdown",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        "xml" => "application/xml",
+        "json" => "application/json",
+        "jsonl" => "application/jsonl",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "zip" => "application/zip",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "pptx" => "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+        _ => return None,
+    };
+    Some(mime)
+}
+
+fn mime_from_magic(head: &[u8]) -> Option<&'static str> {
+    let mime = match head {
+        [b'%', b'P', b'D', b'F', b'-', ..] => "application/pdf",
+        [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, ..] => "image/png",
+        [0xFF, 0xD8, 0xFF, ..] => "image/jpeg",
+        [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => "image/gif",
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => "image/webp",
+        [b'P', b'K', 0x03, 0x04, ..] => "application/zip",
+        _ => return None,
+    };
+    Some(mime)
 }
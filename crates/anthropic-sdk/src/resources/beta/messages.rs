@@ -1,6 +1,7 @@
 use crate::client::{ApiResponse, Inner, RequestOptions};
 use crate::error::Error;
 use crate::streaming::{MessageStream, RawStream};
+use crate::tool_runner::{self, ToolRegistry, ToolRun, ToolRunStream};
 use crate::types::messages::{
     Message, MessageCountTokensParams, MessageCreateParams, MessageTokensCount,
     RawMessageStreamEvent,
@@ -111,6 +112,46 @@ impl Messages {
         Ok(MessageStream::new(raw))
     }
 
+    pub async fn run_tools(
+        &self,
+        params: BetaMessageCreateParams,
+        registry: &ToolRegistry,
+        options: Option<RequestOptions>,
+    ) -> Result<ToolRun, Error> {
+        let betas = params.betas;
+        tool_runner::run(params.body, registry, options, |body, options| {
+            let params = BetaMessageCreateParams {
+                betas: betas.clone(),
+                body,
+            };
+            self.create(params, options)
+        })
+        .await
+    }
+
+    pub fn run_tools_stream(
+        &self,
+        params: BetaMessageCreateParams,
+        registry: &ToolRegistry,
+        options: Option<RequestOptions>,
+    ) -> ToolRunStream {
+        let messages = self.clone();
+        let betas = params.betas;
+        ToolRunStream::new(
+            params.body,
+            registry.clone(),
+            options,
+            move |body, options| {
+                let messages = messages.clone();
+                let params = BetaMessageCreateParams {
+                    betas: betas.clone(),
+                    body,
+                };
+                Box::pin(async move { messages.stream(params, options).await })
+            },
+        )
+    }
+
     pub async fn count_tokens(
         &self,
         params: BetaMessageCountTokensParams,
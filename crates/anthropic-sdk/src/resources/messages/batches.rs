@@ -1,7 +1,7 @@
-use crate::client::{ApiResponse, Inner, RequestOptions};
+use crate::client::{request_id, ApiResponse, Inner, RequestOptions};
 use crate::error::Error;
 use crate::jsonl::jsonl_stream_from_response;
-use crate::pagination::Page;
+use crate::pagination::{Page, PageRequest, PageStream};
 use crate::streaming::RawStream;
 use crate::types::batches::{
     BatchCreateParams, BatchListParams, DeletedMessageBatch, MessageBatch,
@@ -83,27 +83,26 @@ impl Batches {
         params: Option<BatchListParams>,
         options: Option<RequestOptions>,
     ) -> Result<ApiResponse<Page<MessageBatch>>, Error> {
-        let params = params.unwrap_or_default();
-        let mut query = Vec::new();
-        if let Some(limit) = params.limit {
-            query.push(("limit".to_string(), limit.to_string()));
-        }
-        if let Some(before_id) = params.before_id {
-            query.push(("before_id".to_string(), before_id));
-        }
-        if let Some(after_id) = params.after_id {
-            query.push(("after_id".to_string(), after_id));
-        }
+        Self::page_request(params, options).send(&self.inner).await
+    }
 
-        self.inner
-            .request_json(
-                Method::GET,
-                "/v1/messages/batches",
-                Some(query),
-                Option::<&()>::None,
-                options.unwrap_or_default(),
-            )
-            .await
+    pub fn list_auto_paging(
+        &self,
+        params: Option<BatchListParams>,
+        options: Option<RequestOptions>,
+    ) -> PageStream<MessageBatch> {
+        PageStream::new(self.inner.clone(), Ok(Self::page_request(params, options)))
+    }
+
+    fn page_request(
+        params: Option<BatchListParams>,
+        options: Option<RequestOptions>,
+    ) -> PageRequest {
+        PageRequest::new(
+            "/v1/messages/batches",
+            params.unwrap_or_default(),
+            options.unwrap_or_default(),
+        )
     }
 
     pub async fn delete(
@@ -177,11 +176,7 @@ impl Batches {
             )
             .await?;
 
-        let request_id = response
-            .headers()
-            .get("request-id")
-            .and_then(|v| v.to_str().ok())
-            .map(|s| s.to_string());
+        let request_id = request_id(&response.headers);
         let cancel = tokio_util::sync::CancellationToken::new();
         Ok(jsonl_stream_from_response(response, cancel, request_id))
     }
@@ -4,6 +4,7 @@ use crate::client::{ApiResponse, Inner, RequestOptions};
 use crate::error::Error;
 use crate::streaming::MessageStream;
 use crate::streaming::RawStream;
+use crate::tool_runner::{self, ToolRegistry, ToolRun, ToolRunStream};
 use crate::types::messages::{
     Message, MessageCountTokensParams, MessageCreateParams, MessageTokensCount,
 };
@@ -96,6 +97,31 @@ impl Messages {
         Ok(MessageStream::new(raw))
     }
 
+    pub async fn run_tools(
+        &self,
+        params: MessageCreateParams,
+        registry: &ToolRegistry,
+        options: Option<RequestOptions>,
+    ) -> Result<ToolRun, Error> {
+        tool_runner::run(params, registry, options, |params, options| {
+            self.create(params, options)
+        })
+        .await
+    }
+
+    pub fn run_tools_stream(
+        &self,
+        params: MessageCreateParams,
+        registry: &ToolRegistry,
+        options: Option<RequestOptions>,
+    ) -> ToolRunStream {
+        let messages = self.clone();
+        ToolRunStream::new(params, registry.clone(), options, move |params, options| {
+            let messages = messages.clone();
+            Box::pin(async move { messages.stream(params, options).await })
+        })
+    }
+
     pub async fn count_tokens(
         &self,
         params: MessageCountTokensParams,
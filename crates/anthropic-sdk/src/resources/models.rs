@@ -1,6 +1,6 @@
 use crate::client::{ApiResponse, Inner, RequestOptions};
 use crate::error::Error;
-use crate::pagination::Page;
+use crate::pagination::{Page, PageParams, PageRequest, PageStream};
 use crate::types::models::{ModelInfo, ModelListParams, ModelRetrieveParams};
 use reqwest::header::{HeaderName, HeaderValue};
 use reqwest::Method;
@@ -70,10 +70,25 @@ impl Models {
         params: Option<ModelListParams>,
         options: Option<RequestOptions>,
     ) -> Result<ApiResponse<Page<ModelInfo>>, Error> {
+        Self::page_request(params, options)?.send(&self.inner).await
+    }
+
+    pub fn list_auto_paging(
+        &self,
+        params: Option<ModelListParams>,
+        options: Option<RequestOptions>,
+    ) -> PageStream<ModelInfo> {
+        PageStream::new(self.inner.clone(), Self::page_request(params, options))
+    }
+
+    fn page_request(
+        params: Option<ModelListParams>,
+        options: Option<RequestOptions>,
+    ) -> Result<PageRequest, Error> {
         let params = params.unwrap_or_default();
         let mut options = options.unwrap_or_default();
 
-        if let Some(betas) = params.betas.clone() {
+        if let Some(betas) = params.betas {
             if !betas.is_empty() {
                 options.headers.insert(
                     HEADER_ANTHROPIC_BETA,
@@ -82,25 +97,11 @@ impl Models {
             }
         }
 
-        let mut query = Vec::new();
-        if let Some(limit) = params.limit {
-            query.push(("limit".to_string(), limit.to_string()));
-        }
-        if let Some(before_id) = params.before_id {
-            query.push(("before_id".to_string(), before_id));
-        }
-        if let Some(after_id) = params.after_id {
-            query.push(("after_id".to_string(), after_id));
-        }
-
-        self.inner
-            .request_json(
-                Method::GET,
-                "/v1/models",
-                Some(query),
-                Option::<&()>::None,
-                options,
-            )
-            .await
+        let params = PageParams {
+            limit: params.limit,
+            before_id: params.before_id,
+            after_id: params.after_id,
+        };
+        Ok(PageRequest::new("/v1/models", params, options))
     }
 }
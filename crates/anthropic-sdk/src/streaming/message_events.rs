@@ -0,0 +1,143 @@
+use crate::client::RateLimitInfo;
+use crate::error::Error;
+use crate::streaming::MessageStream;
+use crate::types::messages::{ContentBlock, Message, RawContentBlockDelta, RawMessageStreamEvent};
+use futures_core::Stream;
+use serde_json::Value;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+#[derive(Debug, Clone)]
+pub enum MessageStreamEvent {
+    Text {
+        delta: String,
+        snapshot: String,
+    },
+    Thinking {
+        delta: String,
+        snapshot: String,
+    },
+    Signature {
+        signature: String,
+    },
+    InputJson {
+        partial: String,
+        snapshot: Value,
+    },
+    Citation {
+        citation: Value,
+        snapshot: Vec<Value>,
+    },
+    ContentBlockDone(ContentBlock),
+    MessageDone(Message),
+}
+
+pub struct MessageEventStream {
+    inner: MessageStream,
+}
+
+impl MessageEventStream {
+    pub(crate) fn new(inner: MessageStream) -> Self {
+        Self { inner }
+    }
+
+    pub fn abort(&self) {
+        self.inner.abort();
+    }
+
+    pub fn request_id(&self) -> Option<&str> {
+        self.inner.request_id()
+    }
+
+    pub fn rate_limit(&self) -> &RateLimitInfo {
+        self.inner.rate_limit()
+    }
+
+    pub fn snapshot(&self) -> Option<&Message> {
+        self.inner.snapshot()
+    }
+
+    pub fn final_message(&self) -> Option<&Message> {
+        self.inner.final_message()
+    }
+
+    fn derive(&self, event: &RawMessageStreamEvent) -> Option<MessageStreamEvent> {
+        let snapshot = self.inner.snapshot();
+        let block = |index: &usize| snapshot.and_then(|s| s.content.get(*index));
+
+        match event {
+            RawMessageStreamEvent::ContentBlockDelta { index, delta } => match delta {
+                RawContentBlockDelta::TextDelta { text } => Some(MessageStreamEvent::Text {
+                    delta: text.clone(),
+                    snapshot: match block(index) {
+                        Some(ContentBlock::Text(b)) => b.text.clone(),
+                        _ => String::new(),
+                    },
+                }),
+                RawContentBlockDelta::ThinkingDelta { thinking } => {
+                    Some(MessageStreamEvent::Thinking {
+                        delta: thinking.clone(),
+                        snapshot: match block(index) {
+                            Some(ContentBlock::Thinking(b)) => b.thinking.clone(),
+                            _ => String::new(),
+                        },
+                    })
+                }
+                RawContentBlockDelta::SignatureDelta { signature } => {
+                    Some(MessageStreamEvent::Signature {
+                        signature: signature.clone(),
+                    })
+                }
+                RawContentBlockDelta::InputJsonDelta { partial_json } => {
+                    Some(MessageStreamEvent::InputJson {
+                        partial: partial_json.clone(),
+                        snapshot: match block(index) {
+                            Some(ContentBlock::ToolUse(b)) => b.input.clone(),
+                            Some(ContentBlock::ServerToolUse(b)) => b.input.clone(),
+                            _ => Value::Null,
+                        },
+                    })
+                }
+                RawContentBlockDelta::CitationsDelta { citation } => {
+                    Some(MessageStreamEvent::Citation {
+                        citation: citation.clone(),
+                        snapshot: match block(index) {
+                            Some(ContentBlock::Text(b)) => b.citations.clone().unwrap_or_default(),
+                            _ => Vec::new(),
+                        },
+                    })
+                }
+                RawContentBlockDelta::Unknown => None,
+            },
+            RawMessageStreamEvent::ContentBlockStop { index } => block(index)
+                .cloned()
+                .map(MessageStreamEvent::ContentBlockDone),
+            RawMessageStreamEvent::MessageStop => self
+                .inner
+                .final_message()
+                .cloned()
+                .map(MessageStreamEvent::MessageDone),
+            _ => None,
+        }
+    }
+}
+
+impl Stream for MessageEventStream {
+    type Item = Result<MessageStreamEvent, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            match Pin::new(&mut this.inner).poll_next(cx) {
+                Poll::Ready(Some(Ok(event))) => {
+                    if let Some(ev) = this.derive(&event) {
+                        return Poll::Ready(Some(Ok(ev)));
+                    }
+                }
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(err))),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
@@ -1,9 +1,14 @@
+use crate::client::RateLimitInfo;
 use crate::error::Error;
-use crate::streaming::RawStream;
-use crate::types::messages::{Message, RawContentBlockDelta, RawMessageStreamEvent};
+use crate::streaming::{parse_partial_json, MessageEventStream, MessageStreamEvent, RawStream};
+use crate::types::messages::{
+    ContentBlock, Message, RawContentBlockDelta, RawMessageStreamEvent, ServerToolUseBlock,
+    ToolUseBlock,
+};
 use futures_core::Stream;
 use futures_util::StreamExt;
 use serde_json::{Map, Value};
+use std::collections::BTreeMap;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
@@ -11,6 +16,7 @@ pub struct MessageStream {
     raw: RawStream<RawMessageStreamEvent>,
     snapshot: Option<Message>,
     final_message: Option<Message>,
+    partial_json: BTreeMap<usize, String>,
 }
 
 impl MessageStream {
@@ -19,6 +25,7 @@ impl MessageStream {
             raw,
             snapshot: None,
             final_message: None,
+            partial_json: BTreeMap::new(),
         }
     }
 
@@ -30,6 +37,10 @@ impl MessageStream {
         self.raw.request_id()
     }
 
+    pub fn rate_limit(&self) -> &RateLimitInfo {
+        self.raw.rate_limit()
+    }
+
     pub fn snapshot(&self) -> Option<&Message> {
         self.snapshot.as_ref()
     }
@@ -38,6 +49,24 @@ impl MessageStream {
         self.final_message.as_ref()
     }
 
+    pub fn partial_json(&self, index: usize) -> Option<&str> {
+        self.partial_json.get(&index).map(String::as_str)
+    }
+
+    pub fn events(self) -> MessageEventStream {
+        MessageEventStream::new(self)
+    }
+
+    pub fn text_stream(self) -> impl Stream<Item = Result<String, Error>> + Send + Unpin {
+        self.events().filter_map(|ev| {
+            futures_util::future::ready(match ev {
+                Ok(MessageStreamEvent::Text { delta, .. }) => Some(Ok(delta)),
+                Ok(_) => None,
+                Err(err) => Some(Err(err)),
+            })
+        })
+    }
+
     pub async fn into_final_message(mut self) -> Result<Message, Error> {
         while let Some(item) = self.next().await {
             item?;
@@ -52,7 +81,7 @@ impl MessageStream {
         })
     }
 
-    fn content_block_mut(snapshot: &mut Message, index: usize) -> Result<&mut Value, Error> {
+    fn content_block_mut(snapshot: &mut Message, index: usize) -> Result<&mut ContentBlock, Error> {
         snapshot
             .content
             .get_mut(index)
@@ -101,11 +130,75 @@ impl MessageStream {
         }
     }
 
+    fn apply_delta(block: &mut ContentBlock, delta: &RawContentBlockDelta) -> Result<(), Error> {
+        match (block, delta) {
+            (ContentBlock::Text(block), RawContentBlockDelta::TextDelta { text }) => {
+                block.text.push_str(text);
+            }
+            (ContentBlock::Text(block), RawContentBlockDelta::CitationsDelta { citation }) => {
+                block
+                    .citations
+                    .get_or_insert_with(Vec::new)
+                    .push(citation.clone());
+            }
+            (ContentBlock::Thinking(block), RawContentBlockDelta::ThinkingDelta { thinking }) => {
+                block.thinking.push_str(thinking);
+            }
+            (ContentBlock::Thinking(block), RawContentBlockDelta::SignatureDelta { signature }) => {
+                block.signature.push_str(signature);
+            }
+            (ContentBlock::Unknown(block), delta) => match delta {
+                RawContentBlockDelta::TextDelta { text } => {
+                    Self::append_string_field(block, "text", text)?
+                }
+                RawContentBlockDelta::ThinkingDelta { thinking } => {
+                    Self::append_string_field(block, "thinking", thinking)?
+                }
+                RawContentBlockDelta::SignatureDelta { signature } => {
+                    Self::append_string_field(block, "signature", signature)?
+                }
+                RawContentBlockDelta::InputJsonDelta { .. } => {}
+                RawContentBlockDelta::CitationsDelta { citation } => {
+                    Self::push_citation(block, citation.clone())?
+                }
+                RawContentBlockDelta::Unknown => {}
+            },
+            (
+                ContentBlock::ToolUse(_) | ContentBlock::ServerToolUse(_),
+                RawContentBlockDelta::InputJsonDelta { .. },
+            ) => {}
+            (_, RawContentBlockDelta::Unknown) => {}
+            (_, delta) => {
+                return Err(Error::InvalidSse(format!(
+                    "unexpected delta for content block: {delta:?}"
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    fn set_input(block: &mut ContentBlock, input: Value) -> Result<(), Error> {
+        match block {
+            ContentBlock::ToolUse(ToolUseBlock { input: slot, .. })
+            | ContentBlock::ServerToolUse(ServerToolUseBlock { input: slot, .. }) => *slot = input,
+            ContentBlock::Unknown(block) => {
+                Self::ensure_object(block)?.insert("input".to_string(), input);
+            }
+            _ => {
+                return Err(Error::InvalidSse(
+                    "input_json_delta for a block without input".to_string(),
+                ))
+            }
+        }
+        Ok(())
+    }
+
     fn handle_event(&mut self, event: &RawMessageStreamEvent) -> Result<(), Error> {
         match event {
             RawMessageStreamEvent::MessageStart { message } => {
                 self.snapshot = Some(message.clone());
                 self.final_message = None;
+                self.partial_json.clear();
             }
             RawMessageStreamEvent::ContentBlockStart {
                 index,
@@ -126,30 +219,32 @@ impl MessageStream {
             RawMessageStreamEvent::ContentBlockDelta { index, delta } => {
                 let snapshot = self.snapshot_mut()?;
                 let block = Self::content_block_mut(snapshot, *index)?;
-                match delta {
-                    RawContentBlockDelta::TextDelta { text } => {
-                        Self::append_string_field(block, "text", text)?
-                    }
-                    RawContentBlockDelta::ThinkingDelta { thinking } => {
-                        Self::append_string_field(block, "thinking", thinking)?
-                    }
-                    RawContentBlockDelta::SignatureDelta { signature } => {
-                        Self::append_string_field(block, "signature", signature)?
-                    }
-                    RawContentBlockDelta::InputJsonDelta { partial_json } => {
-                        let obj = Self::ensure_object(block)?;
-                        obj.insert(
-                            "_partial_json".to_string(),
-                            Value::String(partial_json.clone()),
-                        );
-                    }
-                    RawContentBlockDelta::CitationsDelta { citation } => {
-                        Self::push_citation(block, citation.clone())?
+                Self::apply_delta(block, delta)?;
+
+                if let RawContentBlockDelta::InputJsonDelta { partial_json } = delta {
+                    let buf = self.partial_json.entry(*index).or_default();
+                    buf.push_str(partial_json);
+                    if let Some(input) = parse_partial_json(buf) {
+                        let snapshot = self.snapshot_mut()?;
+                        Self::set_input(Self::content_block_mut(snapshot, *index)?, input)?;
                     }
-                    RawContentBlockDelta::Unknown => {}
                 }
             }
-            RawMessageStreamEvent::ContentBlockStop { .. } => {}
+            RawMessageStreamEvent::ContentBlockStop { index } => {
+                if let Some(buf) = self.partial_json.get(index) {
+                    let input = if buf.trim().is_empty() {
+                        Value::Object(Map::new())
+                    } else {
+                        serde_json::from_str(buf).map_err(|e| {
+                            Error::InvalidSse(format!(
+                                "invalid tool input JSON for content block {index}: {e}"
+                            ))
+                        })?
+                    };
+                    let snapshot = self.snapshot_mut()?;
+                    Self::set_input(Self::content_block_mut(snapshot, *index)?, input)?;
+                }
+            }
             RawMessageStreamEvent::MessageDelta { delta, usage } => {
                 let snapshot = self.snapshot_mut()?;
                 if let Some(v) = &delta.stop_reason {
@@ -164,7 +259,7 @@ impl MessageStream {
                     "container".to_string(),
                     delta.container.clone().unwrap_or(Value::Null),
                 );
-                snapshot.usage = serde_json::to_value(usage)?;
+                snapshot.usage.merge_delta(usage);
             }
             RawMessageStreamEvent::MessageStop => {
                 if let Some(snapshot) = self.snapshot.clone() {
@@ -1,7 +1,11 @@
+mod message_events;
 mod message_stream;
+mod partial_json;
 mod raw_stream;
 mod sse;
 
+pub use crate::streaming::message_events::{MessageEventStream, MessageStreamEvent};
 pub use crate::streaming::message_stream::MessageStream;
+pub use crate::streaming::partial_json::parse_partial_json;
 pub use crate::streaming::raw_stream::RawStream;
 pub use crate::streaming::sse::{SseEvent, SseParser};
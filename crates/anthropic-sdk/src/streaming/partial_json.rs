@@ -0,0 +1,268 @@
+use serde_json::{Map, Number, Value};
+
+// Best-effort parse of a JSON document that may be cut off anywhere. Truncated strings are
+// kept, while truncated keys, numbers and literals are dropped. Returns `None` when nothing
+// usable has arrived yet or when the input is malformed rather than merely incomplete.
+pub fn parse_partial_json(input: &str) -> Option<Value> {
+    let mut parser = Parser {
+        bytes: input.as_bytes(),
+        pos: 0,
+    };
+    match parser.value().ok()? {
+        Parsed::Complete(value) => {
+            parser.skip_ws();
+            if parser.pos == parser.bytes.len() {
+                Some(value)
+            } else {
+                None
+            }
+        }
+        Parsed::Partial(value) => value,
+    }
+}
+
+enum Parsed {
+    Complete(Value),
+    Partial(Option<Value>),
+}
+
+struct Malformed;
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn skip_ws(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn value(&mut self) -> Result<Parsed, Malformed> {
+        self.skip_ws();
+        match self.peek() {
+            None => Ok(Parsed::Partial(None)),
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => Ok(match self.string()? {
+                (s, true) => Parsed::Complete(Value::String(s)),
+                (s, false) => Parsed::Partial(Some(Value::String(s))),
+            }),
+            Some(b't') => self.literal("true", Value::Bool(true)),
+            Some(b'f') => self.literal("false", Value::Bool(false)),
+            Some(b'n') => self.literal("null", Value::Null),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => Err(Malformed),
+        }
+    }
+
+    fn object(&mut self) -> Result<Parsed, Malformed> {
+        self.pos += 1;
+        let mut map = Map::new();
+        loop {
+            self.skip_ws();
+            match self.peek() {
+                None => return Ok(Parsed::Partial(Some(Value::Object(map)))),
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(Parsed::Complete(Value::Object(map)));
+                }
+                Some(b'"') => {}
+                Some(_) => return Err(Malformed),
+            }
+
+            let key = match self.string()? {
+                (key, true) => key,
+                (_, false) => return Ok(Parsed::Partial(Some(Value::Object(map)))),
+            };
+            self.skip_ws();
+            match self.peek() {
+                None => return Ok(Parsed::Partial(Some(Value::Object(map)))),
+                Some(b':') => self.pos += 1,
+                Some(_) => return Err(Malformed),
+            }
+
+            match self.value()? {
+                Parsed::Complete(value) => {
+                    map.insert(key, value);
+                }
+                Parsed::Partial(value) => {
+                    if let Some(value) = value {
+                        map.insert(key, value);
+                    }
+                    return Ok(Parsed::Partial(Some(Value::Object(map))));
+                }
+            }
+
+            self.skip_ws();
+            match self.peek() {
+                None => return Ok(Parsed::Partial(Some(Value::Object(map)))),
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(Parsed::Complete(Value::Object(map)));
+                }
+                Some(_) => return Err(Malformed),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Parsed, Malformed> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        loop {
+            self.skip_ws();
+            match self.peek() {
+                None => return Ok(Parsed::Partial(Some(Value::Array(items)))),
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Parsed::Complete(Value::Array(items)));
+                }
+                Some(_) => {}
+            }
+
+            match self.value()? {
+                Parsed::Complete(value) => items.push(value),
+                Parsed::Partial(value) => {
+                    items.extend(value);
+                    return Ok(Parsed::Partial(Some(Value::Array(items))));
+                }
+            }
+
+            self.skip_ws();
+            match self.peek() {
+                None => return Ok(Parsed::Partial(Some(Value::Array(items)))),
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Parsed::Complete(Value::Array(items)));
+                }
+                Some(_) => return Err(Malformed),
+            }
+        }
+    }
+
+    // Returns the decoded string and whether its closing quote was seen.
+    fn string(&mut self) -> Result<(String, bool), Malformed> {
+        self.pos += 1;
+        let mut out = String::new();
+        let mut run_start = self.pos;
+        loop {
+            let Some(b) = self.peek() else {
+                out.push_str(self.utf8(run_start, self.pos)?);
+                return Ok((out, false));
+            };
+            match b {
+                b'"' => {
+                    out.push_str(self.utf8(run_start, self.pos)?);
+                    self.pos += 1;
+                    return Ok((out, true));
+                }
+                b'\\' => {
+                    out.push_str(self.utf8(run_start, self.pos)?);
+                    match self.escape()? {
+                        Some(c) => out.push(c),
+                        None => return Ok((out, false)),
+                    }
+                    run_start = self.pos;
+                }
+                _ => self.pos += 1,
+            }
+        }
+    }
+
+    // Decodes the escape at the cursor; `None` means the input ends inside it.
+    fn escape(&mut self) -> Result<Option<char>, Malformed> {
+        let Some(kind) = self.bytes.get(self.pos + 1).copied() else {
+            self.pos = self.bytes.len();
+            return Ok(None);
+        };
+        self.pos += 2;
+        let c = match kind {
+            b'"' => '"',
+            b'\\' => '\\',
+            b'/' => '/',
+            b'b' => '\u{8}',
+            b'f' => '\u{c}',
+            b'n' => '\n',
+            b'r' => '\r',
+            b't' => '\t',
+            b'u' => {
+                let Some(high) = self.hex4()? else {
+                    return Ok(None);
+                };
+                if !(0xD800..0xDC00).contains(&high) {
+                    return char::from_u32(high).map(Some).ok_or(Malformed);
+                }
+                if self.pos + 2 > self.bytes.len() {
+                    self.pos = self.bytes.len();
+                    return Ok(None);
+                }
+                if &self.bytes[self.pos..self.pos + 2] != b"\\u" {
+                    return Err(Malformed);
+                }
+                self.pos += 2;
+                let Some(low) = self.hex4()? else {
+                    return Ok(None);
+                };
+                if !(0xDC00..0xE000).contains(&low) {
+                    return Err(Malformed);
+                }
+                let code = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+                return char::from_u32(code).map(Some).ok_or(Malformed);
+            }
+            _ => return Err(Malformed),
+        };
+        Ok(Some(c))
+    }
+
+    fn hex4(&mut self) -> Result<Option<u32>, Malformed> {
+        if self.pos + 4 > self.bytes.len() {
+            self.pos = self.bytes.len();
+            return Ok(None);
+        }
+        let digits = self.utf8(self.pos, self.pos + 4)?;
+        let code = u32::from_str_radix(digits, 16).map_err(|_| Malformed)?;
+        self.pos += 4;
+        Ok(Some(code))
+    }
+
+    fn utf8(&self, start: usize, end: usize) -> Result<&str, Malformed> {
+        std::str::from_utf8(&self.bytes[start..end]).map_err(|_| Malformed)
+    }
+
+    fn literal(&mut self, word: &str, value: Value) -> Result<Parsed, Malformed> {
+        let rest = &self.bytes[self.pos..];
+        if rest.starts_with(word.as_bytes()) {
+            self.pos += word.len();
+            return Ok(Parsed::Complete(value));
+        }
+        if word.as_bytes().starts_with(rest) {
+            self.pos = self.bytes.len();
+            return Ok(Parsed::Partial(None));
+        }
+        Err(Malformed)
+    }
+
+    fn number(&mut self) -> Result<Parsed, Malformed> {
+        let start = self.pos;
+        while let Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') = self.peek() {
+            self.pos += 1;
+        }
+        if self.pos == self.bytes.len() {
+            // More digits may still arrive, so `12` could yet become `123`; leave it out until
+            // something follows it.
+            return Ok(Parsed::Partial(None));
+        }
+        let text = self.utf8(start, self.pos)?;
+        serde_json::from_str::<Number>(text)
+            .map(|n| Parsed::Complete(Value::Number(n)))
+            .map_err(|_| Malformed)
+    }
+}
@@ -1,3 +1,4 @@
+use crate::client::RateLimitInfo;
 use crate::error::Error;
 use futures_core::Stream;
 use std::pin::Pin;
@@ -8,6 +9,7 @@ pub struct RawStream<T> {
     inner: Pin<Box<dyn Stream<Item = Result<T, Error>> + Send>>,
     cancel: CancellationToken,
     request_id: Option<String>,
+    rate_limit: RateLimitInfo,
 }
 
 impl<T> RawStream<T> {
@@ -15,11 +17,13 @@ impl<T> RawStream<T> {
         inner: Pin<Box<dyn Stream<Item = Result<T, Error>> + Send>>,
         cancel: CancellationToken,
         request_id: Option<String>,
+        rate_limit: RateLimitInfo,
     ) -> Self {
         Self {
             inner,
             cancel,
             request_id,
+            rate_limit,
         }
     }
 
@@ -30,6 +34,10 @@ impl<T> RawStream<T> {
     pub fn request_id(&self) -> Option<&str> {
         self.request_id.as_deref()
     }
+
+    pub fn rate_limit(&self) -> &RateLimitInfo {
+        &self.rate_limit
+    }
 }
 
 impl<T> Stream for RawStream<T> {
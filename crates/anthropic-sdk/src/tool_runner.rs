@@ -0,0 +1,386 @@
+use crate::client::RequestOptions;
+use crate::error::Error;
+use crate::streaming::MessageStream;
+use crate::types::messages::{
+    ContentBlock, Message, MessageCreateParams, MessageParam, RawMessageStreamEvent,
+    ToolResultBlock, ToolResultContent, ToolUseBlock,
+};
+use crate::types::tools::Tool;
+use futures_core::Stream;
+use futures_util::future::{join_all, BoxFuture};
+use futures_util::stream::BoxStream;
+use futures_util::StreamExt;
+use serde_json::Value;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+const DEFAULT_MAX_ITERATIONS: usize = 10;
+
+type ToolHandler =
+    Arc<dyn Fn(Value) -> BoxFuture<'static, Result<ToolResultContent, String>> + Send + Sync>;
+
+#[derive(Clone)]
+pub struct ToolRegistry {
+    tools: Vec<Tool>,
+    handlers: BTreeMap<String, ToolHandler>,
+    max_iterations: usize,
+    parallel: bool,
+}
+
+impl Default for ToolRegistry {
+    fn default() -> Self {
+        Self {
+            tools: Vec::new(),
+            handlers: BTreeMap::new(),
+            max_iterations: DEFAULT_MAX_ITERATIONS,
+            parallel: true,
+        }
+    }
+}
+
+impl fmt::Debug for ToolRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ToolRegistry")
+            .field("tools", &self.tools)
+            .field("max_iterations", &self.max_iterations)
+            .field("parallel", &self.parallel)
+            .finish()
+    }
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<F, Fut, O, E>(mut self, tool: Tool, handler: F) -> Self
+    where
+        F: Fn(Value) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<O, E>> + Send + 'static,
+        O: Into<ToolResultContent>,
+        E: fmt::Display,
+    {
+        let handler: ToolHandler = Arc::new(move |input| {
+            let fut = handler(input);
+            Box::pin(async move { fut.await.map(Into::into).map_err(|e| e.to_string()) })
+        });
+        self.handlers.insert(tool.name.clone(), handler);
+        self.tools.retain(|t| t.name != tool.name);
+        self.tools.push(tool);
+        self
+    }
+
+    #[cfg(feature = "schemars")]
+    pub fn register_typed<T, F, Fut, O, E>(
+        self,
+        name: impl Into<String>,
+        description: impl Into<String>,
+        handler: F,
+    ) -> Self
+    where
+        T: schemars::JsonSchema + serde::de::DeserializeOwned + Send + 'static,
+        F: Fn(T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<O, E>> + Send + 'static,
+        O: Into<ToolResultContent> + Send + 'static,
+        E: fmt::Display + Send + 'static,
+    {
+        let tool = Tool::from_type::<T>(name, description);
+        let name = tool.name.clone();
+        let handler = Arc::new(handler);
+        self.register(tool, move |input: Value| {
+            let parsed = serde_json::from_value::<T>(input).map_err(|source| Error::ToolInput {
+                name: name.clone(),
+                source,
+            });
+            let handler = handler.clone();
+            async move {
+                match parsed {
+                    Ok(input) => handler(input)
+                        .await
+                        .map(Into::into)
+                        .map_err(|e| e.to_string()),
+                    Err(err) => Err(err.to_string()),
+                }
+            }
+        })
+    }
+
+    pub fn max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations.max(1);
+        self
+    }
+
+    pub fn parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    pub fn tools(&self) -> &[Tool] {
+        &self.tools
+    }
+
+    pub async fn execute(&self, message: &Message) -> Vec<ContentBlock> {
+        let calls = message.tool_uses();
+        if self.parallel {
+            join_all(calls.into_iter().map(|call| self.call(call))).await
+        } else {
+            let mut out = Vec::with_capacity(calls.len());
+            for call in calls {
+                out.push(self.call(call).await);
+            }
+            out
+        }
+    }
+
+    async fn call(&self, call: &ToolUseBlock) -> ContentBlock {
+        let result = match self.handlers.get(&call.name) {
+            Some(handler) => handler(call.input.clone()).await,
+            None => Err(format!("unknown tool: {}", call.name)),
+        };
+        let (content, is_error) = match result {
+            Ok(content) => (content, None),
+            Err(message) => (ToolResultContent::Text(message), Some(true)),
+        };
+        ContentBlock::ToolResult(ToolResultBlock {
+            tool_use_id: call.id.clone(),
+            content: Some(content),
+            is_error,
+            cache_control: None,
+            extra: BTreeMap::new(),
+        })
+    }
+
+    fn prepare(&self, params: &mut MessageCreateParams) {
+        if params.tools.is_none() && !self.tools.is_empty() {
+            params.tools = Some(self.tools.clone());
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ToolRun {
+    pub message: Message,
+    pub messages: Vec<MessageParam>,
+    pub iterations: usize,
+}
+
+#[derive(Debug, Clone)]
+pub enum ToolRunEvent {
+    Event(RawMessageStreamEvent),
+    Message(Message),
+    ToolResults(MessageParam),
+}
+
+fn wants_tools(message: &Message) -> bool {
+    message.stop_reason.as_deref() == Some("tool_use")
+}
+
+// Every turn sends a different body, so a caller's idempotency key gets a per-turn suffix; reusing
+// it as-is would let the server replay the first turn's response.
+fn turn_options(options: &Option<RequestOptions>, turn: usize) -> Option<RequestOptions> {
+    let mut options = options.clone()?;
+    if let Some(key) = options.idempotency_key.take() {
+        options.idempotency_key = Some(format!("{key}-{turn}"));
+    }
+    Some(options)
+}
+
+pub(crate) async fn run<F, Fut>(
+    mut params: MessageCreateParams,
+    registry: &ToolRegistry,
+    options: Option<RequestOptions>,
+    create: F,
+) -> Result<ToolRun, Error>
+where
+    F: Fn(MessageCreateParams, Option<RequestOptions>) -> Fut,
+    Fut: Future<Output = Result<Message, Error>>,
+{
+    registry.prepare(&mut params);
+    let mut iterations = 0;
+    loop {
+        iterations += 1;
+        let message = create(params.clone(), turn_options(&options, iterations)).await?;
+        params
+            .messages
+            .push(MessageParam::assistant_blocks(message.content.clone()));
+
+        if !wants_tools(&message) {
+            return Ok(ToolRun {
+                message,
+                messages: params.messages,
+                iterations,
+            });
+        }
+        if iterations >= registry.max_iterations {
+            return Err(Error::MaxIterations { iterations });
+        }
+
+        let results = registry.execute(&message).await;
+        params.messages.push(MessageParam::user_blocks(results));
+    }
+}
+
+type StreamFactory = Box<
+    dyn Fn(
+            MessageCreateParams,
+            Option<RequestOptions>,
+        ) -> BoxFuture<'static, Result<MessageStream, Error>>
+        + Send,
+>;
+
+struct RunState {
+    params: MessageCreateParams,
+    registry: ToolRegistry,
+    options: Option<RequestOptions>,
+    create: StreamFactory,
+    stream: Option<MessageStream>,
+    awaiting_tools: Option<Message>,
+    iterations: usize,
+    pending: VecDeque<ToolRunEvent>,
+    done: bool,
+    out_of_iterations: bool,
+}
+
+pub struct ToolRunStream {
+    inner: BoxStream<'static, Result<ToolRunEvent, Error>>,
+    final_message: Option<Message>,
+}
+
+impl ToolRunStream {
+    pub(crate) fn new<F>(
+        mut params: MessageCreateParams,
+        registry: ToolRegistry,
+        options: Option<RequestOptions>,
+        create: F,
+    ) -> Self
+    where
+        F: Fn(
+                MessageCreateParams,
+                Option<RequestOptions>,
+            ) -> BoxFuture<'static, Result<MessageStream, Error>>
+            + Send
+            + 'static,
+    {
+        registry.prepare(&mut params);
+        let state = RunState {
+            params,
+            registry,
+            options,
+            create: Box::new(create),
+            stream: None,
+            awaiting_tools: None,
+            iterations: 0,
+            pending: VecDeque::new(),
+            done: false,
+            out_of_iterations: false,
+        };
+
+        let inner = futures_util::stream::unfold(state, |mut st| async move {
+            loop {
+                if let Some(ev) = st.pending.pop_front() {
+                    return Some((Ok(ev), st));
+                }
+                if st.done {
+                    if std::mem::take(&mut st.out_of_iterations) {
+                        let err = Error::MaxIterations {
+                            iterations: st.iterations,
+                        };
+                        return Some((Err(err), st));
+                    }
+                    return None;
+                }
+
+                if let Some(message) = st.awaiting_tools.take() {
+                    let results = MessageParam::user_blocks(st.registry.execute(&message).await);
+                    st.params.messages.push(results.clone());
+                    return Some((Ok(ToolRunEvent::ToolResults(results)), st));
+                }
+
+                let Some(stream) = st.stream.as_mut() else {
+                    let options = turn_options(&st.options, st.iterations + 1);
+                    match (st.create)(st.params.clone(), options).await {
+                        Ok(stream) => {
+                            st.stream = Some(stream);
+                            st.iterations += 1;
+                            continue;
+                        }
+                        Err(err) => {
+                            st.done = true;
+                            return Some((Err(err), st));
+                        }
+                    }
+                };
+
+                match stream.next().await {
+                    Some(Ok(ev)) => return Some((Ok(ToolRunEvent::Event(ev)), st)),
+                    Some(Err(err)) => {
+                        st.done = true;
+                        return Some((Err(err), st));
+                    }
+                    None => {}
+                }
+
+                let message = match st.stream.take().and_then(|s| s.final_message().cloned()) {
+                    Some(message) => message,
+                    None => {
+                        st.done = true;
+                        return Some((
+                            Err(Error::InvalidSse(
+                                "stream ended without a final message".to_string(),
+                            )),
+                            st,
+                        ));
+                    }
+                };
+                st.params
+                    .messages
+                    .push(MessageParam::assistant_blocks(message.content.clone()));
+
+                if !wants_tools(&message) {
+                    st.done = true;
+                } else if st.iterations >= st.registry.max_iterations {
+                    // Still hand out the last message before reporting the cap.
+                    st.done = true;
+                    st.out_of_iterations = true;
+                } else {
+                    st.awaiting_tools = Some(message.clone());
+                }
+                st.pending.push_back(ToolRunEvent::Message(message));
+            }
+        });
+
+        Self {
+            inner: Box::pin(inner),
+            final_message: None,
+        }
+    }
+
+    pub fn final_message(&self) -> Option<&Message> {
+        self.final_message.as_ref()
+    }
+
+    pub async fn into_final_message(mut self) -> Result<Message, Error> {
+        while let Some(item) = self.next().await {
+            item?;
+        }
+        self.final_message
+            .ok_or_else(|| Error::InvalidSse("stream ended without a final message".to_string()))
+    }
+}
+
+impl Stream for ToolRunStream {
+    type Item = Result<ToolRunEvent, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let item = this.inner.as_mut().poll_next(cx);
+        if let Poll::Ready(Some(Ok(ToolRunEvent::Message(message)))) = &item {
+            this.final_message = Some(message.clone());
+        }
+        item
+    }
+}
@@ -30,3 +30,18 @@ pub struct FileMetadata {
     #[serde(flatten)]
     pub extra: BTreeMap<String, Value>,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct FileListParams {
+    #[serde(default, skip_serializing)]
+    pub betas: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before_id: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after_id: Option<String>,
+}
@@ -1,4 +1,8 @@
-use serde::{Deserialize, Serialize};
+use crate::error::Error;
+use crate::types::shared::CacheControl;
+use crate::types::tools::{Tool, ToolChoice};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::Value;
 use std::collections::BTreeMap;
 
@@ -7,7 +11,7 @@ pub struct Message {
     pub id: String,
 
     #[serde(default)]
-    pub content: Vec<Value>,
+    pub content: Vec<ContentBlock>,
 
     #[serde(default)]
     pub model: String,
@@ -24,13 +28,94 @@ pub struct Message {
     #[serde(rename = "type", default)]
     pub kind: String,
 
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub usage: Usage,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+impl Message {
+    pub fn text(&self) -> String {
+        self.content
+            .iter()
+            .filter_map(ContentBlock::as_text)
+            .collect::<Vec<_>>()
+            .join("")
+    }
+
+    pub fn tool_uses(&self) -> Vec<&ToolUseBlock> {
+        self.content
+            .iter()
+            .filter_map(ContentBlock::as_tool_use)
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Usage {
+    #[serde(default)]
+    pub input_tokens: u64,
+
     #[serde(default)]
-    pub usage: Value,
+    pub output_tokens: u64,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_creation_input_tokens: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_read_input_tokens: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_tool_use: Option<ServerToolUsage>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_tier: Option<String>,
 
     #[serde(flatten)]
     pub extra: BTreeMap<String, Value>,
 }
 
+impl Usage {
+    // `message_delta` usage is cumulative, but fields the delta leaves unset must keep
+    // the values reported by `message_start`.
+    pub(crate) fn merge_delta(&mut self, delta: &MessageDeltaUsage) {
+        self.output_tokens = delta.output_tokens;
+        if let Some(v) = delta.input_tokens {
+            self.input_tokens = v;
+        }
+        if let Some(v) = delta.cache_creation_input_tokens {
+            self.cache_creation_input_tokens = Some(v);
+        }
+        if let Some(v) = delta.cache_read_input_tokens {
+            self.cache_read_input_tokens = Some(v);
+        }
+        if let Some(v) = &delta.server_tool_use {
+            self.server_tool_use = Some(v.clone());
+        }
+        for (key, value) in &delta.extra {
+            self.extra.insert(key.clone(), value.clone());
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ServerToolUsage {
+    #[serde(default)]
+    pub web_search_requests: u64,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Default,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MessageTokensCount {
     pub input_tokens: u64,
@@ -56,13 +141,307 @@ impl MessageParam {
             content: MessageContent::Text(text.into()),
         }
     }
+
+    pub fn user_blocks(blocks: Vec<ContentBlock>) -> Self {
+        Self {
+            role: "user".to_string(),
+            content: MessageContent::Blocks(blocks),
+        }
+    }
+
+    pub fn assistant_blocks(blocks: Vec<ContentBlock>) -> Self {
+        Self {
+            role: "assistant".to_string(),
+            content: MessageContent::Blocks(blocks),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum MessageContent {
     Text(String),
-    Blocks(Vec<Value>),
+    Blocks(Vec<ContentBlock>),
+}
+
+impl From<String> for MessageContent {
+    fn from(text: String) -> Self {
+        Self::Text(text)
+    }
+}
+
+impl From<&str> for MessageContent {
+    fn from(text: &str) -> Self {
+        Self::Text(text.to_string())
+    }
+}
+
+impl From<Vec<ContentBlock>> for MessageContent {
+    fn from(blocks: Vec<ContentBlock>) -> Self {
+        Self::Blocks(blocks)
+    }
+}
+
+// Block types this SDK does not model yet deserialize into `Unknown` instead of failing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ContentBlock {
+    #[serde(rename = "text")]
+    Text(TextBlock),
+
+    #[serde(rename = "image")]
+    Image(ImageBlock),
+
+    #[serde(rename = "document")]
+    Document(DocumentBlock),
+
+    #[serde(rename = "tool_use")]
+    ToolUse(ToolUseBlock),
+
+    #[serde(rename = "tool_result")]
+    ToolResult(ToolResultBlock),
+
+    #[serde(rename = "thinking")]
+    Thinking(ThinkingBlock),
+
+    #[serde(rename = "redacted_thinking")]
+    RedactedThinking(RedactedThinkingBlock),
+
+    #[serde(rename = "server_tool_use")]
+    ServerToolUse(ServerToolUseBlock),
+
+    #[serde(rename = "web_search_tool_result")]
+    WebSearchToolResult(WebSearchToolResultBlock),
+
+    #[serde(untagged)]
+    Unknown(Value),
+}
+
+impl ContentBlock {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text(TextBlock {
+            text: text.into(),
+            ..Default::default()
+        })
+    }
+
+    pub fn tool_result(
+        tool_use_id: impl Into<String>,
+        content: impl Into<ToolResultContent>,
+    ) -> Self {
+        Self::ToolResult(ToolResultBlock {
+            tool_use_id: tool_use_id.into(),
+            content: Some(content.into()),
+            is_error: None,
+            cache_control: None,
+            extra: BTreeMap::new(),
+        })
+    }
+
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Self::Text(block) => Some(&block.text),
+            _ => None,
+        }
+    }
+
+    pub fn as_tool_use(&self) -> Option<&ToolUseBlock> {
+        match self {
+            Self::ToolUse(block) => Some(block),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct TextBlock {
+    pub text: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub citations: Option<Vec<Value>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControl>,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageBlock {
+    pub source: ImageSource,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControl>,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ImageSource {
+    #[serde(rename = "base64")]
+    Base64 { media_type: String, data: String },
+
+    #[serde(rename = "url")]
+    Url { url: String },
+
+    #[serde(rename = "file")]
+    File { file_id: String },
+
+    #[serde(untagged)]
+    Unknown(Value),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocumentBlock {
+    pub source: DocumentSource,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub citations: Option<Value>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControl>,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum DocumentSource {
+    #[serde(rename = "base64")]
+    Base64 { media_type: String, data: String },
+
+    #[serde(rename = "text")]
+    Text { media_type: String, data: String },
+
+    #[serde(rename = "url")]
+    Url { url: String },
+
+    #[serde(rename = "content")]
+    Content { content: MessageContent },
+
+    #[serde(rename = "file")]
+    File { file_id: String },
+
+    #[serde(untagged)]
+    Unknown(Value),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolUseBlock {
+    pub id: String,
+    pub name: String,
+
+    #[serde(default)]
+    pub input: Value,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControl>,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+impl ToolUseBlock {
+    pub fn parse_input<T: DeserializeOwned>(&self) -> Result<T, Error> {
+        serde_json::from_value(self.input.clone()).map_err(|source| Error::ToolInput {
+            name: self.name.clone(),
+            source,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolResultBlock {
+    pub tool_use_id: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<ToolResultContent>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_error: Option<bool>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControl>,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ToolResultContent {
+    Text(String),
+    Blocks(Vec<ContentBlock>),
+}
+
+impl From<String> for ToolResultContent {
+    fn from(text: String) -> Self {
+        Self::Text(text)
+    }
+}
+
+impl From<&str> for ToolResultContent {
+    fn from(text: &str) -> Self {
+        Self::Text(text.to_string())
+    }
+}
+
+impl From<Vec<ContentBlock>> for ToolResultContent {
+    fn from(blocks: Vec<ContentBlock>) -> Self {
+        Self::Blocks(blocks)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ThinkingBlock {
+    pub thinking: String,
+
+    #[serde(default)]
+    pub signature: String,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedactedThinkingBlock {
+    pub data: String,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerToolUseBlock {
+    pub id: String,
+    pub name: String,
+
+    #[serde(default)]
+    pub input: Value,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebSearchToolResultBlock {
+    pub tool_use_id: String,
+
+    #[serde(default)]
+    pub content: Value,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -71,6 +450,33 @@ pub struct MessageCreateParams {
     pub max_tokens: u64,
     pub messages: Vec<MessageParam>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<SystemPrompt>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Metadata>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_sequences: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<ThinkingConfig>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<Tool>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ToolChoice>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stream: Option<bool>,
 
@@ -78,11 +484,67 @@ pub struct MessageCreateParams {
     pub extra: BTreeMap<String, Value>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SystemPrompt {
+    Text(String),
+    Blocks(Vec<ContentBlock>),
+}
+
+impl From<String> for SystemPrompt {
+    fn from(text: String) -> Self {
+        Self::Text(text)
+    }
+}
+
+impl From<&str> for SystemPrompt {
+    fn from(text: &str) -> Self {
+        Self::Text(text.to_string())
+    }
+}
+
+impl From<Vec<ContentBlock>> for SystemPrompt {
+    fn from(blocks: Vec<ContentBlock>) -> Self {
+        Self::Blocks(blocks)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Metadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ThinkingConfig {
+    #[serde(rename = "enabled")]
+    Enabled { budget_tokens: u64 },
+
+    #[serde(rename = "disabled")]
+    Disabled,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct MessageCountTokensParams {
     pub model: String,
     pub messages: Vec<MessageParam>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<SystemPrompt>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<ThinkingConfig>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<Tool>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ToolChoice>,
+
     #[serde(flatten)]
     pub extra: BTreeMap<String, Value>,
 }
@@ -103,7 +565,10 @@ pub enum RawMessageStreamEvent {
     MessageStop,
 
     #[serde(rename = "content_block_start")]
-    ContentBlockStart { index: usize, content_block: Value },
+    ContentBlockStart {
+        index: usize,
+        content_block: ContentBlock,
+    },
 
     #[serde(rename = "content_block_delta")]
     ContentBlockDelta {
@@ -130,7 +595,7 @@ pub struct MessageDeltaUsage {
     pub output_tokens: u64,
 
     #[serde(default)]
-    pub server_tool_use: Option<Value>,
+    pub server_tool_use: Option<ServerToolUsage>,
 
     #[serde(flatten)]
     pub extra: BTreeMap<String, Value>,
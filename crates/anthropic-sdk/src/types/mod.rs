@@ -4,3 +4,4 @@ pub mod files;
 pub mod messages;
 pub mod models;
 pub mod shared;
+pub mod tools;
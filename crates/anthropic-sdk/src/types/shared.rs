@@ -25,3 +25,19 @@ pub struct ErrorResponse {
     #[serde(flatten)]
     pub extra: BTreeMap<String, Value>,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum CacheControl {
+    #[serde(rename = "ephemeral")]
+    Ephemeral {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        ttl: Option<String>,
+    },
+}
+
+impl CacheControl {
+    pub fn ephemeral() -> Self {
+        Self::Ephemeral { ttl: None }
+    }
+}
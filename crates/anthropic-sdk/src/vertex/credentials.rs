@@ -0,0 +1,260 @@
+use crate::client::{HttpBody, HttpRequest, HttpTransport};
+use crate::error::Error;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine as _;
+use futures_util::future::BoxFuture;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use reqwest::{Method, Url};
+use ring::rand::SystemRandom;
+use ring::signature::{RsaKeyPair, RSA_PKCS1_SHA256};
+use serde::Deserialize;
+use serde_json::json;
+use std::fmt;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+const DEFAULT_TOKEN_URI: &str = "https://oauth2.googleapis.com/token";
+const DEFAULT_METADATA_HOST: &str = "metadata.google.internal";
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct AccessToken {
+    pub token: String,
+    pub expires_at: Option<SystemTime>,
+}
+
+impl fmt::Debug for AccessToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccessToken")
+            .field("token", &"[redacted]")
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+impl AccessToken {
+    pub fn new(token: impl Into<String>) -> Self {
+        Self {
+            token: token.into(),
+            expires_at: None,
+        }
+    }
+
+    pub fn expires_at(mut self, expires_at: SystemTime) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+}
+
+// Supplies OAuth access tokens for Vertex AI. The client caches the returned token until shortly
+// before `expires_at`, so implementations may hit the network on every call. Token requests
+// should go through `http`, the client's configured transport, so they share its proxy and TLS
+// settings.
+pub trait TokenProvider: fmt::Debug + Send + Sync {
+    fn token<'a>(
+        &'a self,
+        http: &'a dyn HttpTransport,
+    ) -> BoxFuture<'a, Result<AccessToken, Error>>;
+}
+
+impl TokenProvider for AccessToken {
+    fn token<'a>(
+        &'a self,
+        _http: &'a dyn HttpTransport,
+    ) -> BoxFuture<'a, Result<AccessToken, Error>> {
+        Box::pin(async move { Ok(self.clone()) })
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: Option<u64>,
+}
+
+impl TokenResponse {
+    fn into_token(self) -> AccessToken {
+        AccessToken {
+            token: self.access_token,
+            expires_at: self
+                .expires_in
+                .map(|secs| SystemTime::now() + Duration::from_secs(secs)),
+        }
+    }
+}
+
+async fn fetch_token(http: &dyn HttpTransport, request: HttpRequest) -> Result<AccessToken, Error> {
+    let response = http.send(request).await?;
+    let status = response.status;
+    let body = response.bytes().await?;
+    if !status.is_success() {
+        return Err(Error::Credentials(format!(
+            "token request failed with {status}: {}",
+            String::from_utf8_lossy(&body)
+        )));
+    }
+    Ok(serde_json::from_slice::<TokenResponse>(&body)?.into_token())
+}
+
+// A service account key as downloaded from the Google Cloud console, exchanged for access tokens
+// with a self-signed JWT assertion.
+#[derive(Clone, Deserialize)]
+pub struct ServiceAccountKey {
+    pub client_email: String,
+    pub private_key: String,
+    #[serde(default)]
+    pub private_key_id: Option<String>,
+    #[serde(default)]
+    pub project_id: Option<String>,
+    #[serde(default)]
+    pub token_uri: Option<String>,
+}
+
+impl fmt::Debug for ServiceAccountKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServiceAccountKey")
+            .field("client_email", &self.client_email)
+            .field("project_id", &self.project_id)
+            .field("token_uri", &self.token_uri)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ServiceAccountKey {
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| Error::Credentials(format!("failed to read {}: {e}", path.display())))?;
+        Self::from_json(&json)
+    }
+
+    fn assertion(&self, token_uri: &str) -> Result<String, Error> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let header = json!({"alg": "RS256", "typ": "JWT", "kid": self.private_key_id});
+        let claims = json!({
+            "iss": self.client_email,
+            "scope": SCOPE,
+            "aud": token_uri,
+            "iat": now,
+            "exp": now + 3600,
+        });
+        let signing_input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header.to_string()),
+            URL_SAFE_NO_PAD.encode(claims.to_string())
+        );
+
+        let der = pem_to_der(&self.private_key)?;
+        let key = RsaKeyPair::from_pkcs8(&der)
+            .map_err(|e| Error::Credentials(format!("invalid service account key: {e}")))?;
+        let mut signature = vec![0; key.public().modulus_len()];
+        key.sign(
+            &RSA_PKCS1_SHA256,
+            &SystemRandom::new(),
+            signing_input.as_bytes(),
+            &mut signature,
+        )
+        .map_err(|_| Error::Credentials("failed to sign token assertion".to_string()))?;
+        Ok(format!(
+            "{signing_input}.{}",
+            URL_SAFE_NO_PAD.encode(signature)
+        ))
+    }
+}
+
+impl TokenProvider for ServiceAccountKey {
+    fn token<'a>(
+        &'a self,
+        http: &'a dyn HttpTransport,
+    ) -> BoxFuture<'a, Result<AccessToken, Error>> {
+        Box::pin(async move {
+            let token_uri = self.token_uri.as_deref().unwrap_or(DEFAULT_TOKEN_URI);
+            let assertion = self.assertion(token_uri)?;
+            let body = format!(
+                "grant_type=urn%3Aietf%3Aparams%3Aoauth%3Agrant-type%3Ajwt-bearer&assertion={assertion}"
+            );
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                CONTENT_TYPE,
+                HeaderValue::from_static("application/x-www-form-urlencoded"),
+            );
+            let request = HttpRequest {
+                method: Method::POST,
+                url: Url::parse(token_uri)?,
+                headers,
+                body: Some(HttpBody::Bytes(body.into())),
+            };
+            fetch_token(http, request).await
+        })
+    }
+}
+
+fn pem_to_der(pem: &str) -> Result<Vec<u8>, Error> {
+    let encoded: String = pem
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .flat_map(|line| line.trim().chars())
+        .collect();
+    STANDARD
+        .decode(encoded)
+        .map_err(|e| Error::Credentials(format!("invalid service account key: {e}")))
+}
+
+// The GCE/GKE/Cloud Run metadata server, which hands out tokens for the attached service account.
+#[derive(Debug, Clone)]
+pub struct MetadataServer {
+    base_url: String,
+}
+
+impl Default for MetadataServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MetadataServer {
+    // Honors `GCE_METADATA_HOST` like the Google client libraries do.
+    pub fn new() -> Self {
+        let host = std::env::var("GCE_METADATA_HOST")
+            .unwrap_or_else(|_| DEFAULT_METADATA_HOST.to_string());
+        Self {
+            base_url: format!("http://{host}"),
+        }
+    }
+
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+}
+
+impl TokenProvider for MetadataServer {
+    fn token<'a>(
+        &'a self,
+        http: &'a dyn HttpTransport,
+    ) -> BoxFuture<'a, Result<AccessToken, Error>> {
+        Box::pin(async move {
+            let url = Url::parse(&self.base_url)?
+                .join("/computeMetadata/v1/instance/service-accounts/default/token")?;
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                HeaderName::from_static("metadata-flavor"),
+                HeaderValue::from_static("Google"),
+            );
+            let request = HttpRequest {
+                method: Method::GET,
+                url,
+                headers,
+                body: None,
+            };
+            fetch_token(http, request).await
+        })
+    }
+}
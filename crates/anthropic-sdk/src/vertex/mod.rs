@@ -0,0 +1,278 @@
+mod credentials;
+
+pub use crate::vertex::credentials::{
+    AccessToken, MetadataServer, ServiceAccountKey, TokenProvider,
+};
+
+use crate::client::{
+    ClientOptions, Credentials, HttpBody, HttpRequest, HttpResponse, HttpTransport, Inner,
+    RefreshingCredentials, RetryPolicy, DEFAULT_TIMEOUT,
+};
+use crate::error::Error;
+use crate::middleware::Middleware;
+use crate::resources::messages::Messages;
+use bytes::Bytes;
+use futures_util::future::BoxFuture;
+use reqwest::header::{HeaderValue, CONTENT_TYPE};
+use reqwest::{Client as HttpClient, Method};
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+
+const VERTEX_VERSION: &str = "vertex-2023-10-16";
+
+// Messages API served by Google Vertex AI. `messages.create`, `messages.stream` and
+// `messages.count_tokens` are available; other endpoints fail without sending a request.
+#[derive(Clone)]
+pub struct AnthropicVertex {
+    pub messages: Messages,
+}
+
+impl AnthropicVertex {
+    pub fn builder() -> VertexBuilder {
+        VertexBuilder::new()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VertexBuilder {
+    region: Option<String>,
+    project_id: Option<String>,
+    token_provider: Option<Arc<dyn TokenProvider>>,
+    options: ClientOptions,
+}
+
+impl Default for VertexBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VertexBuilder {
+    pub fn new() -> Self {
+        Self {
+            region: None,
+            project_id: None,
+            token_provider: None,
+            options: ClientOptions::empty(),
+        }
+    }
+
+    pub fn region(mut self, region: impl Into<String>) -> Self {
+        self.region = Some(region.into());
+        self
+    }
+
+    pub fn project_id(mut self, project_id: impl Into<String>) -> Self {
+        self.project_id = Some(project_id.into());
+        self
+    }
+
+    pub fn token_provider(mut self, provider: Arc<dyn TokenProvider>) -> Self {
+        self.token_provider = Some(provider);
+        self
+    }
+
+    // Uses the key's `project_id` unless one is set explicitly.
+    pub fn service_account(mut self, key: ServiceAccountKey) -> Self {
+        if self.project_id.is_none() {
+            self.project_id = key.project_id.clone();
+        }
+        self.token_provider = Some(Arc::new(key));
+        self
+    }
+
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.options.base_url = Some(base_url.into());
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.options.timeout = Some(timeout);
+        self
+    }
+
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.options.max_retries = Some(max_retries);
+        self
+    }
+
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.options.retry_policy = Some(retry_policy);
+        self
+    }
+
+    pub fn http_client(mut self, http_client: HttpClient) -> Self {
+        self.options.http_client = Some(http_client);
+        self
+    }
+
+    pub fn transport(mut self, transport: Arc<dyn HttpTransport>) -> Self {
+        self.options.transport = Some(transport);
+        self
+    }
+
+    pub fn middleware(mut self, middleware: Arc<dyn Middleware>) -> Self {
+        self.options.middleware.push(middleware);
+        self
+    }
+
+    // Region and project fall back to `CLOUD_ML_REGION` and `ANTHROPIC_VERTEX_PROJECT_ID`. Without
+    // a token provider, the key named by `GOOGLE_APPLICATION_CREDENTIALS` is used if set, and the
+    // metadata server otherwise.
+    pub fn build(self) -> Result<AnthropicVertex, Error> {
+        let mut project_id = self.project_id;
+        let token_provider: Arc<dyn TokenProvider> = match self.token_provider {
+            Some(provider) => provider,
+            None => match std::env::var("GOOGLE_APPLICATION_CREDENTIALS") {
+                Ok(path) => {
+                    let key = ServiceAccountKey::from_file(path)?;
+                    project_id = project_id.or_else(|| key.project_id.clone());
+                    Arc::new(key)
+                }
+                Err(_) => Arc::new(MetadataServer::new()),
+            },
+        };
+        let region = self
+            .region
+            .or_else(|| std::env::var("CLOUD_ML_REGION").ok())
+            .ok_or_else(|| {
+                Error::Internal(
+                    "vertex region is missing; set region or CLOUD_ML_REGION".to_string(),
+                )
+            })?;
+        let project_id = project_id
+            .or_else(|| std::env::var("ANTHROPIC_VERTEX_PROJECT_ID").ok())
+            .ok_or_else(|| {
+                Error::Internal(
+                    "vertex project is missing; set project_id or ANTHROPIC_VERTEX_PROJECT_ID"
+                        .to_string(),
+                )
+            })?;
+
+        let mut options = self.options;
+        if options.base_url.is_none() {
+            options.base_url = Some(std::env::var("ANTHROPIC_VERTEX_BASE_URL").unwrap_or_else(
+                |_| match region.as_str() {
+                    "global" => "https://aiplatform.googleapis.com".to_string(),
+                    region => format!("https://{region}-aiplatform.googleapis.com"),
+                },
+            ));
+        }
+
+        // Tokens are fetched through the same transport as API requests, and cached and refreshed
+        // (including after a 401) like any other credentials.
+        let transport = Inner::base_transport(&mut options)?;
+        let token_timeout = options.timeout.unwrap_or(DEFAULT_TIMEOUT);
+        let http = transport.clone();
+        options.transport = Some(transport);
+        options.credential_provider = Some(Arc::new(RefreshingCredentials::new(move || {
+            let (provider, http) = (token_provider.clone(), http.clone());
+            Box::pin(async move {
+                let token = tokio::time::timeout(token_timeout, provider.token(&*http))
+                    .await
+                    .map_err(|_| Error::Timeout)??;
+                let credentials = Credentials::auth_token(token.token);
+                Ok(match token.expires_at {
+                    Some(expires_at) => credentials.expires_at(expires_at),
+                    None => credentials,
+                })
+            })
+        })));
+
+        let inner = Inner::with_transport_auth(options, |transport| {
+            Arc::new(VertexTransport {
+                inner: transport,
+                region,
+                project_id,
+            })
+        })?;
+        Ok(AnthropicVertex {
+            messages: Messages::new(Arc::new(inner)),
+        })
+    }
+}
+
+// Rewrites Messages API requests into `rawPredict`/`streamRawPredict` calls. The bearer token is
+// added by the client from its credential provider; responses, including event streams, pass
+// through unchanged.
+#[derive(Debug)]
+struct VertexTransport {
+    inner: Arc<dyn HttpTransport>,
+    region: String,
+    project_id: String,
+}
+
+impl HttpTransport for VertexTransport {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, Error>> {
+        Box::pin(async move {
+            let request = self.prepare(request)?;
+            self.inner.send(request).await
+        })
+    }
+}
+
+impl VertexTransport {
+    fn prepare(&self, mut request: HttpRequest) -> Result<HttpRequest, Error> {
+        let path = request.url.path().to_string();
+        let (prefix, count_tokens) = match request.method {
+            Method::POST if path.ends_with("/v1/messages") => {
+                (&path[..path.len() - "/v1/messages".len()], false)
+            }
+            Method::POST if path.ends_with("/v1/messages/count_tokens") => (
+                &path[..path.len() - "/v1/messages/count_tokens".len()],
+                true,
+            ),
+            _ => {
+                return Err(Error::Internal(format!(
+                    "{} {path} is not supported on Vertex AI",
+                    request.method
+                )))
+            }
+        };
+
+        let bytes = request
+            .body
+            .as_ref()
+            .and_then(HttpBody::as_bytes)
+            .ok_or_else(|| Error::Internal("vertex requests need a JSON body".to_string()))?;
+        let mut body = serde_json::from_slice::<Value>(bytes)?;
+        let object = body
+            .as_object_mut()
+            .ok_or_else(|| Error::Internal("vertex requests need a JSON object".to_string()))?;
+        object
+            .entry("anthropic_version")
+            .or_insert_with(|| Value::from(VERTEX_VERSION));
+
+        let model_and_method = if count_tokens {
+            "count-tokens:rawPredict".to_string()
+        } else {
+            let model = match object.remove("model") {
+                Some(Value::String(model)) => model,
+                _ => return Err(Error::Internal("vertex requests need a model".to_string())),
+            };
+            let stream = object.get("stream") == Some(&Value::Bool(true));
+            let method = if stream {
+                "streamRawPredict"
+            } else {
+                "rawPredict"
+            };
+            format!("{model}:{method}")
+        };
+
+        let new_path = format!(
+            "{prefix}/v1/projects/{}/locations/{}/publishers/anthropic/models/{model_and_method}",
+            self.project_id, self.region
+        );
+        request.url.set_path(&new_path);
+        request.url.set_query(None);
+        request
+            .headers
+            .remove(reqwest::header::HeaderName::from_static("x-api-key"));
+        request
+            .headers
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        request.body = Some(HttpBody::Bytes(Bytes::from(serde_json::to_vec(&body)?)));
+        Ok(request)
+    }
+}
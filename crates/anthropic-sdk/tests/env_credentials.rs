@@ -0,0 +1,42 @@
+// Kept in its own test binary: setting environment variables while other test threads may be
+// reading them is a data race, so this must be the only test in the process.
+
+use anthropic_sdk::{Anthropic, EnvCredentials, Error};
+use serde_json::json;
+use std::sync::Arc;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+#[tokio::test]
+async fn env_credentials_read_the_variable_on_every_request() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/v1/models"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(
+            json!({"data": [], "has_more": false, "first_id": null, "last_id": null}),
+        ))
+        .mount(&server)
+        .await;
+
+    std::env::set_var("SDK_TEST_ROTATING_KEY", "env-key-1");
+    let client = Anthropic::builder()
+        .base_url(server.uri())
+        .credential_provider(Arc::new(EnvCredentials::api_key("SDK_TEST_ROTATING_KEY")))
+        .build()
+        .unwrap();
+    client.models.list(None, None).await.unwrap();
+    std::env::set_var("SDK_TEST_ROTATING_KEY", "env-key-2");
+    client.models.list(None, None).await.unwrap();
+    std::env::remove_var("SDK_TEST_ROTATING_KEY");
+    assert!(matches!(
+        client.models.list(None, None).await,
+        Err(Error::Credentials(_))
+    ));
+
+    let reqs = server.received_requests().await.unwrap();
+    let keys: Vec<_> = reqs
+        .iter()
+        .map(|r| r.headers.get("x-api-key").unwrap().to_str().unwrap())
+        .collect();
+    assert_eq!(keys, ["env-key-1", "env-key-2"]);
+}
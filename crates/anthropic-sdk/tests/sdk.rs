@@ -1,33 +1,46 @@
-use anthropic_sdk::resources::beta::files::FileUploadParams;
+use anthropic_sdk::middleware::{self, Middleware, Next};
+use anthropic_sdk::resources::beta::files::{FileUploadParams, UploadProgress};
 use anthropic_sdk::resources::beta::messages::{
     BetaMessageCountTokensParams, BetaMessageCreateParams,
 };
-use anthropic_sdk::types::batches::MessageBatchResult;
+use anthropic_sdk::streaming::{parse_partial_json, MessageStreamEvent};
+use anthropic_sdk::tool_runner::{ToolRegistry, ToolRunEvent};
+use anthropic_sdk::types::batches::{BatchCreateParams, MessageBatchResult};
+use anthropic_sdk::types::files::FileListParams;
 use anthropic_sdk::types::messages::{
-    MessageCountTokensParams, MessageCreateParams, MessageParam, RawMessageStreamEvent,
+    ContentBlock, Message, MessageContent, MessageCountTokensParams, MessageCreateParams,
+    MessageParam, RawMessageStreamEvent, SystemPrompt, TextBlock, ThinkingConfig,
+    ToolResultContent,
 };
 use anthropic_sdk::types::models::ModelListParams;
-use anthropic_sdk::{Anthropic, ClientOptions, Error};
+use anthropic_sdk::types::shared::CacheControl;
+use anthropic_sdk::types::tools::{Tool, ToolChoice};
+use anthropic_sdk::{
+    Anthropic, CircuitBreaker, CircuitScope, CircuitState, ClientOptions, Credentials, Error,
+    ErrorKind, FileCredentials, HttpApiError, HttpConfig, HttpRequest, HttpResponse, HttpTransport,
+    RateLimiter, RefreshingCredentials, RequestOptions, RetryPolicy,
+};
+use bytes::Bytes;
+use futures_util::future::BoxFuture;
 use futures_util::StreamExt;
-use reqwest::header::HeaderMap;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Method, StatusCode};
 use serde_json::json;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use wiremock::matchers::{method, path};
+use wiremock::matchers::{method, path, query_param, query_param_is_missing};
 use wiremock::{Mock, MockServer, Request, Respond, ResponseTemplate};
 
 fn client_for(server: &MockServer) -> Anthropic {
-    Anthropic::new(ClientOptions {
-        api_key: Some("test-key".to_string()),
-        auth_token: None,
-        base_url: Some(server.uri()),
-        timeout: Some(Duration::from_millis(200)),
-        max_retries: Some(2),
-        default_headers: HeaderMap::new(),
-    })
-    .unwrap()
+    Anthropic::builder()
+        .api_key("test-key")
+        .base_url(server.uri())
+        .timeout(Duration::from_millis(200))
+        .max_retries(2)
+        .build()
+        .unwrap()
 }
 
 #[tokio::test]
@@ -147,10 +160,11 @@ async fn beta_files_upload_is_multipart_and_injects_files_beta() {
         .files
         .upload(
             FileUploadParams {
-                path: file_path.clone(),
+                file: file_path.clone().into(),
                 filename: None,
                 mime_type: Some("text/plain".to_string()),
                 betas: None,
+                on_progress: None,
             },
             None,
         )
@@ -181,6 +195,236 @@ async fn beta_files_upload_is_multipart_and_injects_files_beta() {
     std::fs::remove_file(&file_path).unwrap();
 }
 
+#[tokio::test]
+async fn beta_files_upload_streams_sources_and_detects_mime_types() {
+    let server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/files"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+          "id": "file_1",
+          "created_at": "2025-01-01T00:00:00Z",
+          "filename": "notes.md",
+          "mime_type": "text/markdown",
+          "size_bytes": 5,
+          "type": "file"
+        })))
+        .mount(&server)
+        .await;
+    let fail_once = || {
+        Mock::given(method("POST"))
+            .and(path("/v1/files"))
+            .respond_with(ResponseTemplate::new(500))
+            .with_priority(1)
+            .up_to_n_times(1)
+    };
+
+    let client = client_for(&server);
+    let part_header = |req: &Request| {
+        let body = String::from_utf8_lossy(&req.body).to_string();
+        body.split("\r\n\r\n").next().unwrap().to_string()
+    };
+
+    // Paths are streamed from disk again on every attempt.
+    fail_once().mount(&server).await;
+    let file_path = write_temp_file("anthropic-sdk-upload", b"# hello");
+    let progress = Arc::new(Mutex::new(Vec::new()));
+    let seen = progress.clone();
+    let params = FileUploadParams::path(&file_path)
+        .filename("notes.md")
+        .on_progress(move |p| seen.lock().unwrap().push(p));
+    client.beta.files.upload(params, None).await.unwrap();
+
+    let reqs = server.received_requests().await.unwrap();
+    assert_eq!(reqs.len(), 2);
+    for req in &reqs {
+        let header = part_header(req);
+        assert!(header.contains("filename=\"notes.md\""));
+        assert!(header.contains("Content-Type: text/markdown"));
+        assert!(String::from_utf8_lossy(&req.body).contains("# hello"));
+        // The size of a file on disk is known up front, so the body is not sent chunked.
+        assert_eq!(
+            req.headers.get("content-length").unwrap().to_str().unwrap(),
+            req.body.len().to_string()
+        );
+        assert!(req.headers.get("transfer-encoding").is_none());
+    }
+    let total = reqs[1].body.len() as u64;
+    let progress = progress.lock().unwrap().clone();
+    assert_eq!(
+        progress.last().unwrap(),
+        &UploadProgress {
+            bytes_sent: total,
+            total_bytes: Some(total),
+        }
+    );
+    std::fs::remove_file(&file_path).unwrap();
+
+    // Without a filename extension the content itself is sniffed.
+    let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+    client
+        .beta
+        .files
+        .upload(FileUploadParams::bytes(png), None)
+        .await
+        .unwrap();
+    let reqs = server.received_requests().await.unwrap();
+    let header = part_header(&reqs[2]);
+    assert!(header.contains("filename=\"file\""));
+    assert!(header.contains("Content-Type: image/png"));
+
+    // Readers can only be sent once, so they are never retried.
+    let reader: &'static [u8] = b"%PDF-1.7 body";
+    client
+        .beta
+        .files
+        .upload(FileUploadParams::reader(reader), None)
+        .await
+        .unwrap();
+    fail_once().mount(&server).await;
+    let err = client
+        .beta
+        .files
+        .upload(FileUploadParams::reader(reader), None)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::Http(_)), "{err:?}");
+    let reqs = server.received_requests().await.unwrap();
+    assert_eq!(reqs.len(), 5);
+    assert!(part_header(&reqs[3]).contains("Content-Type: application/pdf"));
+    assert!(String::from_utf8_lossy(&reqs[3].body).contains("%PDF-1.7 body"));
+    assert!(reqs[3].headers.get("content-length").is_none());
+}
+
+#[tokio::test]
+async fn reader_uploads_report_the_401_when_the_body_cannot_be_replayed() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/files"))
+        .respond_with(ResponseTemplate::new(401).set_body_json(json!({
+          "type": "error",
+          "error": {"type": "authentication_error", "message": "invalid x-api-key"}
+        })))
+        .mount(&server)
+        .await;
+
+    let provider =
+        RefreshingCredentials::new(|| Box::pin(async { Ok(Credentials::api_key("stale-key")) }));
+    let client = Anthropic::builder()
+        .credential_provider(Arc::new(provider))
+        .base_url(server.uri())
+        .build()
+        .unwrap();
+    let reader: &'static [u8] = b"hello";
+    let err = client
+        .beta
+        .files
+        .upload(FileUploadParams::reader(reader), None)
+        .await
+        .unwrap_err();
+
+    match err {
+        Error::Http(http) => {
+            assert_eq!(http.api_error().status, Some(StatusCode::UNAUTHORIZED));
+            assert_eq!(
+                http.api_error().message.as_deref(),
+                Some("invalid x-api-key")
+            );
+        }
+        other => panic!("expected the 401, got {other:?}"),
+    }
+    assert_eq!(server.received_requests().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn fixed_credentials_are_not_resent_after_a_401() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/v1/models"))
+        .respond_with(ResponseTemplate::new(401).set_body_json(json!({
+          "type": "error",
+          "error": {"type": "authentication_error", "message": "invalid x-api-key"}
+        })))
+        .mount(&server)
+        .await;
+
+    let client = Anthropic::builder()
+        .credential_provider(Arc::new(Credentials::api_key("stale-key")))
+        .base_url(server.uri())
+        .build()
+        .unwrap();
+    assert!(client.models.list(None, None).await.is_err());
+    assert_eq!(server.received_requests().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn beta_files_list_pages_with_params_and_betas() {
+    let server = MockServer::start().await;
+
+    let file = |id: &str| {
+        json!({
+          "id": id,
+          "created_at": "2025-01-01T00:00:00Z",
+          "filename": "test.txt",
+          "mime_type": "text/plain",
+          "size_bytes": 5,
+          "type": "file"
+        })
+    };
+    Mock::given(method("GET"))
+        .and(path("/v1/files"))
+        .and(query_param_is_missing("after_id"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+          "data": [file("file_1")],
+          "has_more": true,
+          "first_id": "file_1",
+          "last_id": "file_1"
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/v1/files"))
+        .and(query_param("after_id", "file_1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+          "data": [file("file_2")],
+          "has_more": false,
+          "first_id": "file_2",
+          "last_id": "file_2"
+        })))
+        .mount(&server)
+        .await;
+
+    let client = client_for(&server);
+    let page = client
+        .beta
+        .files
+        .list(
+            Some(FileListParams {
+                betas: Some(vec!["foo".into()]),
+                limit: Some(1),
+                ..Default::default()
+            }),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(page.data[0].id, "file_1");
+    let page = page.next_page(&client).await.unwrap().unwrap();
+    assert_eq!(page.data[0].id, "file_2");
+    assert!(!page.has_next_page());
+
+    let reqs = server.received_requests().await.unwrap();
+    assert_eq!(reqs.len(), 2);
+    assert_eq!(reqs[0].url.query(), Some("limit=1"));
+    assert_eq!(reqs[1].url.query(), Some("limit=1&after_id=file_1"));
+    for req in &reqs {
+        assert_eq!(
+            req.headers.get("anthropic-beta").unwrap(),
+            "foo,files-api-2025-04-14"
+        );
+    }
+}
+
 fn write_temp_file(name: &str, contents: &[u8]) -> PathBuf {
     use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -197,15 +441,12 @@ fn write_temp_file(name: &str, contents: &[u8]) -> PathBuf {
 #[tokio::test]
 async fn auth_missing_errors() {
     let server = MockServer::start().await;
-    let client = Anthropic::new(ClientOptions {
-        api_key: None,
-        auth_token: None,
-        base_url: Some(server.uri()),
-        timeout: Some(Duration::from_millis(200)),
-        max_retries: Some(0),
-        default_headers: HeaderMap::new(),
-    })
-    .unwrap();
+    let client = Anthropic::builder()
+        .base_url(server.uri())
+        .timeout(Duration::from_millis(200))
+        .max_retries(0)
+        .build()
+        .unwrap();
 
     let err = client.models.list(None, None).await.unwrap_err();
     match err {
@@ -266,6 +507,98 @@ async fn models_list_sends_headers_and_beta() {
     assert!(h.contains_key("x-stainless-timeout"));
 }
 
+fn model_page(ids: &[&str], has_more: bool) -> ResponseTemplate {
+    let data: Vec<_> = ids
+        .iter()
+        .map(|id| {
+            json!({
+              "id": id,
+              "created_at": "2025-01-01T00:00:00Z",
+              "display_name": id,
+              "type": "model"
+            })
+        })
+        .collect();
+    ResponseTemplate::new(200).set_body_json(json!({
+      "data": data,
+      "has_more": has_more,
+      "first_id": ids.first(),
+      "last_id": ids.last()
+    }))
+}
+
+#[tokio::test]
+async fn pages_are_walked_forwards_and_backwards() {
+    let server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/v1/models"))
+        .and(query_param_is_missing("after_id"))
+        .and(query_param_is_missing("before_id"))
+        .respond_with(model_page(&["model_1", "model_2"], true))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/v1/models"))
+        .and(query_param("after_id", "model_2"))
+        .respond_with(model_page(&["model_3"], false))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/v1/models"))
+        .and(query_param("before_id", "model_3"))
+        .respond_with(model_page(&["model_2"], true))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/v1/models"))
+        .and(query_param("before_id", "model_2"))
+        .respond_with(model_page(&["model_1"], false))
+        .mount(&server)
+        .await;
+
+    let client = client_for(&server);
+    let ids: Vec<String> = client
+        .models
+        .list_auto_paging(
+            Some(ModelListParams {
+                betas: Some(vec!["foo".into()]),
+                limit: Some(2),
+                ..Default::default()
+            }),
+            None,
+        )
+        .map(|model| model.unwrap().id)
+        .collect()
+        .await;
+    assert_eq!(ids, ["model_1", "model_2", "model_3"]);
+
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[1].url.query(), Some("limit=2&after_id=model_2"));
+    assert_eq!(requests[1].headers.get("anthropic-beta").unwrap(), "foo");
+
+    let page = client
+        .models
+        .list(
+            Some(ModelListParams {
+                before_id: Some("model_3".into()),
+                limit: Some(1),
+                ..Default::default()
+            }),
+            None,
+        )
+        .await
+        .unwrap();
+    let page = page.next_page(&client).await.unwrap().unwrap();
+    assert_eq!(page.data[0].id, "model_1");
+    assert!(page.next_page(&client).await.unwrap().is_none());
+
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 4);
+    assert_eq!(requests[3].url.query(), Some("limit=1&before_id=model_2"));
+}
+
 #[derive(Clone)]
 struct SequenceResponder {
     calls: Arc<AtomicUsize>,
@@ -384,15 +717,13 @@ async fn message_stream_aggregates_text_and_final_message() {
         if let RawMessageStreamEvent::ContentBlockDelta { .. } = ev {
             saw_text_delta = true;
             let snap = stream.snapshot().unwrap();
-            let block = snap.content[0].as_object().unwrap();
-            assert_eq!(block.get("text").unwrap().as_str().unwrap(), "Hello");
+            assert_eq!(snap.content[0].as_text(), Some("Hello"));
         }
     }
     assert!(saw_text_delta);
 
     let final_msg = stream.final_message().unwrap();
-    let block = final_msg.content[0].as_object().unwrap();
-    assert_eq!(block.get("text").unwrap().as_str().unwrap(), "Hello");
+    assert_eq!(final_msg.text(), "Hello");
     assert_eq!(final_msg.stop_reason.as_deref(), Some("end_turn"));
 }
 
@@ -463,9 +794,1976 @@ async fn batch_results_jsonl_streams() {
     assert_eq!(out[0].custom_id, "req1");
     match &out[0].result {
         MessageBatchResult::Succeeded { message } => {
-            let block = message.content[0].as_object().unwrap();
-            assert_eq!(block.get("text").unwrap().as_str().unwrap(), "A");
+            assert_eq!(message.content[0].as_text(), Some("A"));
         }
         _ => panic!("expected succeeded"),
     }
 }
+
+#[test]
+fn content_blocks_deserialize_typed_with_unknown_fallback() {
+    let message: Message = serde_json::from_value(json!({
+      "id": "msg_1",
+      "type": "message",
+      "role": "assistant",
+      "model": "test-model",
+      "content": [
+        {"type": "thinking", "thinking": "hmm", "signature": "sig"},
+        {"type": "text", "text": "Let me check. ", "citations": null},
+        {"type": "tool_use", "id": "toolu_1", "name": "get_weather", "input": {"city": "Paris"}},
+        {"type": "text", "text": "One moment."},
+        {"type": "brand_new_block", "payload": 1}
+      ]
+    }))
+    .unwrap();
+
+    assert!(matches!(message.content[0], ContentBlock::Thinking(ref b) if b.signature == "sig"));
+    assert_eq!(message.text(), "Let me check. One moment.");
+
+    let tool_uses = message.tool_uses();
+    assert_eq!(tool_uses.len(), 1);
+    assert_eq!(tool_uses[0].name, "get_weather");
+    assert_eq!(tool_uses[0].input, json!({"city": "Paris"}));
+
+    match &message.content[4] {
+        ContentBlock::Unknown(v) => assert_eq!(v["type"], "brand_new_block"),
+        other => panic!("expected unknown block, got {other:?}"),
+    }
+
+    let reply = MessageParam::user_blocks(vec![ContentBlock::tool_result("toolu_1", "sunny")]);
+    assert_eq!(
+        serde_json::to_value(&reply).unwrap(),
+        json!({
+          "role": "user",
+          "content": [{"type": "tool_result", "tool_use_id": "toolu_1", "content": "sunny"}]
+        })
+    );
+
+    let parsed: MessageParam = serde_json::from_value(json!({
+      "role": "user",
+      "content": [{"type": "tool_result", "tool_use_id": "toolu_1", "content": [{"type": "text", "text": "ok"}]}]
+    }))
+    .unwrap();
+    match parsed.content {
+        MessageContent::Blocks(blocks) => match &blocks[0] {
+            ContentBlock::ToolResult(block) => {
+                assert!(matches!(block.content, Some(ToolResultContent::Blocks(_))))
+            }
+            other => panic!("expected tool_result, got {other:?}"),
+        },
+        other => panic!("expected blocks, got {other:?}"),
+    }
+}
+
+fn sse_body(events: &[serde_json::Value]) -> String {
+    events
+        .iter()
+        .map(|ev| format!("event: {}\ndata: {}\n\n", ev["type"].as_str().unwrap(), ev))
+        .collect()
+}
+
+async fn mount_sse(server: &MockServer, events: &[serde_json::Value]) {
+    Mock::given(method("POST"))
+        .and(path("/v1/messages"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "text/event-stream")
+                .set_body_string(sse_body(events)),
+        )
+        .mount(server)
+        .await;
+}
+
+fn stream_params() -> MessageCreateParams {
+    MessageCreateParams {
+        model: "test-model".to_string(),
+        max_tokens: 16,
+        messages: vec![MessageParam::user("hi")],
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn message_stream_merges_usage_from_message_start() {
+    let server = MockServer::start().await;
+    mount_sse(
+        &server,
+        &[
+            json!({"type": "message_start", "message": {
+              "id": "msg_1", "type": "message", "role": "assistant", "model": "test-model",
+              "content": [],
+              "usage": {"input_tokens": 25, "output_tokens": 1, "cache_read_input_tokens": 10, "service_tier": "standard"}
+            }}),
+            json!({"type": "message_delta",
+              "delta": {"stop_reason": "end_turn", "stop_sequence": null},
+              "usage": {"input_tokens": null, "output_tokens": 7, "cache_read_input_tokens": null, "server_tool_use": {"web_search_requests": 2}}
+            }),
+            json!({"type": "message_stop"}),
+        ],
+    )
+    .await;
+
+    let client = client_for(&server);
+    let message = client
+        .messages
+        .stream(stream_params(), None)
+        .await
+        .unwrap()
+        .into_final_message()
+        .await
+        .unwrap();
+
+    assert_eq!(message.usage.input_tokens, 25);
+    assert_eq!(message.usage.output_tokens, 7);
+    assert_eq!(message.usage.cache_read_input_tokens, Some(10));
+    assert_eq!(message.usage.service_tier.as_deref(), Some("standard"));
+    assert_eq!(
+        message
+            .usage
+            .server_tool_use
+            .as_ref()
+            .map(|u| u.web_search_requests),
+        Some(2)
+    );
+    // Unset optional fields are left out rather than written as `null`.
+    assert_eq!(
+        serde_json::to_value(&message.usage).unwrap(),
+        json!({
+          "input_tokens": 25,
+          "output_tokens": 7,
+          "cache_read_input_tokens": 10,
+          "server_tool_use": {"web_search_requests": 2},
+          "service_tier": "standard"
+        })
+    );
+}
+
+#[tokio::test]
+async fn messages_create_serializes_typed_params() {
+    let server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/messages"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+          "id": "msg_1",
+          "type": "message"
+        })))
+        .mount(&server)
+        .await;
+
+    let client = client_for(&server);
+    let _ = client
+        .messages
+        .create(
+            MessageCreateParams {
+                model: "test-model".to_string(),
+                max_tokens: 2048,
+                messages: vec![MessageParam::user("What's the weather?")],
+                system: Some(SystemPrompt::Blocks(vec![ContentBlock::Text(TextBlock {
+                    text: "You are terse.".to_string(),
+                    cache_control: Some(CacheControl::ephemeral()),
+                    ..Default::default()
+                })])),
+                temperature: Some(0.5),
+                stop_sequences: Some(vec!["STOP".to_string()]),
+                thinking: Some(ThinkingConfig::Enabled {
+                    budget_tokens: 1024,
+                }),
+                tools: Some(vec![Tool::new(
+                    "get_weather",
+                    "Get the weather for a city",
+                    json!({"type": "object", "properties": {"city": {"type": "string"}}}),
+                )]),
+                tool_choice: Some(ToolChoice::tool("get_weather")),
+                ..Default::default()
+            },
+            None,
+        )
+        .await
+        .unwrap();
+
+    let reqs = server.received_requests().await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&reqs[0].body).unwrap();
+    assert_eq!(
+        body,
+        json!({
+          "model": "test-model",
+          "max_tokens": 2048,
+          "messages": [{"role": "user", "content": "What's the weather?"}],
+          "system": [{"type": "text", "text": "You are terse.", "cache_control": {"type": "ephemeral"}}],
+          "temperature": 0.5,
+          "stop_sequences": ["STOP"],
+          "thinking": {"type": "enabled", "budget_tokens": 1024},
+          "tools": [{
+            "name": "get_weather",
+            "description": "Get the weather for a city",
+            "input_schema": {"type": "object", "properties": {"city": {"type": "string"}}}
+          }],
+          "tool_choice": {"type": "tool", "name": "get_weather"}
+        })
+    );
+}
+
+#[cfg(feature = "schemars")]
+#[test]
+fn tool_schema_from_type_and_typed_input() {
+    use anthropic_sdk::types::messages::ToolUseBlock;
+
+    #[derive(schemars::JsonSchema, serde::Deserialize, Debug, PartialEq)]
+    struct GetWeather {
+        city: String,
+        unit: Option<String>,
+    }
+
+    let tool = Tool::from_type::<GetWeather>("get_weather", "Get the weather");
+    assert_eq!(tool.name, "get_weather");
+    assert!(tool.input_schema.get("$schema").is_none());
+    assert_eq!(tool.input_schema["type"], "object");
+    assert_eq!(tool.input_schema["required"], json!(["city"]));
+
+    let block: ToolUseBlock = serde_json::from_value(json!({
+      "id": "toolu_1", "name": "get_weather", "input": {"city": "Paris"}
+    }))
+    .unwrap();
+    assert_eq!(
+        block.parse_input::<GetWeather>().unwrap(),
+        GetWeather {
+            city: "Paris".to_string(),
+            unit: None
+        }
+    );
+
+    let bad: ToolUseBlock = serde_json::from_value(json!({
+      "id": "toolu_2", "name": "get_weather", "input": {"town": "Paris"}
+    }))
+    .unwrap();
+    match bad.parse_input::<GetWeather>().unwrap_err() {
+        Error::ToolInput { name, source } => {
+            assert_eq!(name, "get_weather");
+            assert!(source.to_string().contains("city"));
+        }
+        other => panic!("expected ToolInput, got {other:?}"),
+    }
+}
+
+struct ScriptedResponder {
+    calls: Arc<AtomicUsize>,
+    responses: Vec<ResponseTemplate>,
+}
+
+impl Respond for ScriptedResponder {
+    fn respond(&self, _request: &Request) -> ResponseTemplate {
+        let n = self.calls.fetch_add(1, Ordering::SeqCst);
+        self.responses[n.min(self.responses.len() - 1)].clone()
+    }
+}
+
+fn weather_tools() -> ToolRegistry {
+    ToolRegistry::new()
+        .register(
+            Tool::new("get_weather", "Get the weather", json!({"type": "object"})),
+            |input: serde_json::Value| async move {
+                Ok::<_, String>(format!(
+                    "sunny in {}",
+                    input["city"].as_str().unwrap_or("?")
+                ))
+            },
+        )
+        .register(
+            Tool::new("get_time", "Get the time", json!({"type": "object"})),
+            |_input: serde_json::Value| async move { Err::<String, _>("clock is broken") },
+        )
+}
+
+#[tokio::test]
+async fn run_tools_executes_tool_calls_until_end_turn() {
+    let server = MockServer::start().await;
+    let tool_turn = json!({
+      "id": "msg_1", "type": "message", "role": "assistant", "model": "test-model",
+      "stop_reason": "tool_use",
+      "content": [
+        {"type": "tool_use", "id": "toolu_1", "name": "get_weather", "input": {"city": "Paris"}},
+        {"type": "tool_use", "id": "toolu_2", "name": "get_time", "input": {}},
+        {"type": "tool_use", "id": "toolu_3", "name": "get_mood", "input": {}}
+      ]
+    });
+    let final_turn = json!({
+      "id": "msg_2", "type": "message", "role": "assistant", "model": "test-model",
+      "stop_reason": "end_turn",
+      "content": [{"type": "text", "text": "It is sunny."}]
+    });
+
+    Mock::given(method("POST"))
+        .and(path("/v1/messages"))
+        .respond_with(ScriptedResponder {
+            calls: Arc::new(AtomicUsize::new(0)),
+            responses: vec![
+                ResponseTemplate::new(200).set_body_json(tool_turn),
+                ResponseTemplate::new(200).set_body_json(final_turn),
+            ],
+        })
+        .mount(&server)
+        .await;
+
+    let client = client_for(&server);
+    let run = client
+        .messages
+        .run_tools(
+            stream_params(),
+            &weather_tools(),
+            Some(RequestOptions::default().idempotency_key("abc")),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(run.iterations, 2);
+    assert_eq!(run.message.text(), "It is sunny.");
+    assert_eq!(run.messages.len(), 4);
+
+    let reqs = server.received_requests().await.unwrap();
+    assert_eq!(reqs.len(), 2);
+    // Each turn has a different body, so each gets its own idempotency key.
+    let keys: Vec<_> = reqs
+        .iter()
+        .map(|r| r.headers.get("idempotency-key").unwrap().to_str().unwrap())
+        .collect();
+    assert_eq!(keys, ["abc-1", "abc-2"]);
+    let first: serde_json::Value = serde_json::from_slice(&reqs[0].body).unwrap();
+    assert_eq!(first["tools"][0]["name"], "get_weather");
+    assert_eq!(first["tools"][1]["name"], "get_time");
+
+    let second: serde_json::Value = serde_json::from_slice(&reqs[1].body).unwrap();
+    assert_eq!(second["messages"][1]["role"], "assistant");
+    assert_eq!(
+        second["messages"][2],
+        json!({
+          "role": "user",
+          "content": [
+            {"type": "tool_result", "tool_use_id": "toolu_1", "content": "sunny in Paris"},
+            {"type": "tool_result", "tool_use_id": "toolu_2", "content": "clock is broken", "is_error": true},
+            {"type": "tool_result", "tool_use_id": "toolu_3", "content": "unknown tool: get_mood", "is_error": true}
+          ]
+        })
+    );
+}
+
+#[tokio::test]
+async fn run_tools_stream_yields_events_across_iterations() {
+    let server = MockServer::start().await;
+    let tool_turn = sse_body(&[
+        json!({"type": "message_start", "message": {
+          "id": "msg_1", "type": "message", "role": "assistant", "model": "test-model", "content": []
+        }}),
+        json!({"type": "content_block_start", "index": 0, "content_block": {
+          "type": "tool_use", "id": "toolu_1", "name": "get_weather", "input": {}
+        }}),
+        json!({"type": "content_block_delta", "index": 0, "delta": {
+          "type": "input_json_delta", "partial_json": "{\"city\": \"Paris\"}"
+        }}),
+        json!({"type": "content_block_stop", "index": 0}),
+        json!({"type": "message_delta", "delta": {"stop_reason": "tool_use"}, "usage": {"output_tokens": 3}}),
+        json!({"type": "message_stop"}),
+    ]);
+    let final_turn = sse_body(&[
+        json!({"type": "message_start", "message": {
+          "id": "msg_2", "type": "message", "role": "assistant", "model": "test-model", "content": []
+        }}),
+        json!({"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}}),
+        json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "Sunny."}}),
+        json!({"type": "content_block_stop", "index": 0}),
+        json!({"type": "message_delta", "delta": {"stop_reason": "end_turn"}, "usage": {"output_tokens": 2}}),
+        json!({"type": "message_stop"}),
+    ]);
+
+    Mock::given(method("POST"))
+        .and(path("/v1/messages"))
+        .respond_with(ScriptedResponder {
+            calls: Arc::new(AtomicUsize::new(0)),
+            responses: vec![
+                ResponseTemplate::new(200)
+                    .insert_header("content-type", "text/event-stream")
+                    .set_body_string(tool_turn),
+                ResponseTemplate::new(200)
+                    .insert_header("content-type", "text/event-stream")
+                    .set_body_string(final_turn),
+            ],
+        })
+        .mount(&server)
+        .await;
+
+    let client = client_for(&server);
+    let mut stream = client.messages.run_tools_stream(
+        stream_params(),
+        &weather_tools(),
+        Some(RequestOptions::default().idempotency_key("abc")),
+    );
+
+    let mut messages = 0;
+    let mut tool_results = Vec::new();
+    while let Some(item) = stream.next().await {
+        match item.unwrap() {
+            ToolRunEvent::Message(_) => messages += 1,
+            ToolRunEvent::ToolResults(results) => tool_results.push(results),
+            ToolRunEvent::Event(_) => {}
+        }
+    }
+    assert_eq!(messages, 2);
+    assert_eq!(tool_results.len(), 1);
+    assert_eq!(
+        serde_json::to_value(&tool_results[0]).unwrap()["content"][0]["content"],
+        "sunny in Paris"
+    );
+    assert_eq!(stream.final_message().unwrap().text(), "Sunny.");
+
+    let reqs = server.received_requests().await.unwrap();
+    assert_eq!(reqs.len(), 2);
+    let keys: Vec<_> = reqs
+        .iter()
+        .map(|r| r.headers.get("idempotency-key").unwrap().to_str().unwrap())
+        .collect();
+    assert_eq!(keys, ["abc-1", "abc-2"]);
+}
+
+#[tokio::test]
+async fn run_tools_reports_hitting_max_iterations() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/messages"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+          "id": "msg_1", "type": "message", "role": "assistant", "model": "test-model",
+          "stop_reason": "tool_use",
+          "content": [{"type": "tool_use", "id": "toolu_1", "name": "get_weather", "input": {}}]
+        })))
+        .mount(&server)
+        .await;
+
+    let client = client_for(&server);
+    let tools = weather_tools().max_iterations(2);
+    let err = client
+        .messages
+        .run_tools(stream_params(), &tools, None)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::MaxIterations { iterations: 2 }));
+    assert_eq!(server.received_requests().await.unwrap().len(), 2);
+
+    server.reset().await;
+    mount_sse(
+        &server,
+        &[
+            json!({"type": "message_start", "message": {
+              "id": "msg_1", "type": "message", "role": "assistant", "model": "test-model", "content": []
+            }}),
+            json!({"type": "content_block_start", "index": 0, "content_block": {
+              "type": "tool_use", "id": "toolu_1", "name": "get_weather", "input": {}
+            }}),
+            json!({"type": "content_block_stop", "index": 0}),
+            json!({"type": "message_delta", "delta": {"stop_reason": "tool_use"}, "usage": {"output_tokens": 3}}),
+            json!({"type": "message_stop"}),
+        ],
+    )
+    .await;
+
+    let mut stream = client
+        .messages
+        .run_tools_stream(stream_params(), &tools, None);
+    let mut messages = 0;
+    let mut last = None;
+    while let Some(item) = stream.next().await {
+        match item {
+            Ok(ToolRunEvent::Message(_)) => messages += 1,
+            Ok(_) => {}
+            Err(err) => last = Some(err),
+        }
+    }
+    assert_eq!(messages, 2);
+    assert!(matches!(last, Some(Error::MaxIterations { iterations: 2 })));
+    assert_eq!(stream.final_message().unwrap().id, "msg_1");
+    assert_eq!(server.received_requests().await.unwrap().len(), 2);
+}
+
+#[test]
+fn partial_json_parses_truncated_documents() {
+    assert_eq!(parse_partial_json(""), None);
+    assert_eq!(parse_partial_json("{"), Some(json!({})));
+    assert_eq!(parse_partial_json("{\"ci"), Some(json!({})));
+    assert_eq!(parse_partial_json("{\"city\": "), Some(json!({})));
+    assert_eq!(
+        parse_partial_json("{\"city\": \"Par"),
+        Some(json!({"city": "Par"}))
+    );
+    assert_eq!(
+        parse_partial_json("{\"city\": \"Paris\", \"days\": [1, 2"),
+        Some(json!({"city": "Paris", "days": [1]}))
+    );
+    assert_eq!(
+        parse_partial_json("{\"city\": \"Paris\", \"days\": [1, 2]"),
+        Some(json!({"city": "Paris", "days": [1, 2]}))
+    );
+    // A number at the very end may still grow, so it is held back.
+    assert_eq!(parse_partial_json("{\"n\": 12"), Some(json!({})));
+    assert_eq!(parse_partial_json("{\"n\": 12,"), Some(json!({"n": 12})));
+    assert_eq!(parse_partial_json("12"), None);
+    assert_eq!(parse_partial_json("{\"n\": -1.5e"), Some(json!({})));
+    assert_eq!(parse_partial_json("{\"ok\": tr"), Some(json!({})));
+    assert_eq!(
+        parse_partial_json("{\"s\": \"a\\n\\u00e9\\"),
+        Some(json!({"s": "a\né"}))
+    );
+    assert_eq!(
+        parse_partial_json("{\"a\": [{\"b\": null}, true]}"),
+        Some(json!({"a": [{"b": null}, true]}))
+    );
+    assert_eq!(parse_partial_json("{\"a\" 1"), None);
+    assert_eq!(parse_partial_json("{} x"), None);
+}
+
+#[tokio::test]
+async fn message_stream_accumulates_tool_input_json() {
+    let server = MockServer::start().await;
+    mount_sse(
+        &server,
+        &[
+            json!({"type": "message_start", "message": {
+              "id": "msg_1", "type": "message", "role": "assistant", "model": "test-model", "content": []
+            }}),
+            json!({"type": "content_block_start", "index": 0, "content_block": {
+              "type": "tool_use", "id": "toolu_1", "name": "get_weather", "input": {}
+            }}),
+            json!({"type": "content_block_delta", "index": 0, "delta": {
+              "type": "input_json_delta", "partial_json": "{\"city\": \"Pa"
+            }}),
+            json!({"type": "content_block_delta", "index": 0, "delta": {
+              "type": "input_json_delta", "partial_json": "ris\", \"days\": [1, "
+            }}),
+            json!({"type": "content_block_delta", "index": 0, "delta": {
+              "type": "input_json_delta", "partial_json": "2]}"
+            }}),
+            json!({"type": "content_block_stop", "index": 0}),
+            json!({"type": "message_delta", "delta": {"stop_reason": "tool_use"}, "usage": {"output_tokens": 9}}),
+            json!({"type": "message_stop"}),
+        ],
+    )
+    .await;
+
+    let client = client_for(&server);
+    let mut stream = client.messages.stream(stream_params(), None).await.unwrap();
+
+    let mut inputs = Vec::new();
+    while let Some(item) = stream.next().await {
+        if let RawMessageStreamEvent::ContentBlockDelta { .. } = item.unwrap() {
+            let snap = stream.snapshot().unwrap();
+            inputs.push(snap.content[0].as_tool_use().unwrap().input.clone());
+        }
+    }
+    assert_eq!(
+        inputs,
+        vec![
+            json!({"city": "Pa"}),
+            json!({"city": "Paris", "days": [1]}),
+            json!({"city": "Paris", "days": [1, 2]}),
+        ]
+    );
+    assert_eq!(
+        stream.partial_json(0),
+        Some("{\"city\": \"Paris\", \"days\": [1, 2]}")
+    );
+
+    let final_msg = stream.final_message().unwrap();
+    let tool_use = final_msg.tool_uses()[0];
+    assert_eq!(tool_use.input, json!({"city": "Paris", "days": [1, 2]}));
+    assert!(tool_use.extra.is_empty());
+}
+
+fn thinking_and_text_events() -> Vec<serde_json::Value> {
+    vec![
+        json!({"type": "message_start", "message": {
+          "id": "msg_1", "type": "message", "role": "assistant", "model": "test-model", "content": []
+        }}),
+        json!({"type": "content_block_start", "index": 0, "content_block": {"type": "thinking", "thinking": "", "signature": ""}}),
+        json!({"type": "content_block_delta", "index": 0, "delta": {"type": "thinking_delta", "thinking": "Hmm"}}),
+        json!({"type": "content_block_delta", "index": 0, "delta": {"type": "signature_delta", "signature": "sig"}}),
+        json!({"type": "content_block_stop", "index": 0}),
+        json!({"type": "content_block_start", "index": 1, "content_block": {"type": "text", "text": ""}}),
+        json!({"type": "content_block_delta", "index": 1, "delta": {"type": "text_delta", "text": "Hello"}}),
+        json!({"type": "content_block_delta", "index": 1, "delta": {"type": "citations_delta", "citation": {"type": "char_location", "cited_text": "x"}}}),
+        json!({"type": "content_block_delta", "index": 1, "delta": {"type": "text_delta", "text": ", world"}}),
+        json!({"type": "content_block_stop", "index": 1}),
+        json!({"type": "message_delta", "delta": {"stop_reason": "end_turn"}, "usage": {"output_tokens": 4}}),
+        json!({"type": "message_stop"}),
+    ]
+}
+
+#[tokio::test]
+async fn message_stream_events_derive_high_level_events() {
+    let server = MockServer::start().await;
+    mount_sse(&server, &thinking_and_text_events()).await;
+
+    let client = client_for(&server);
+    let mut events = client
+        .messages
+        .stream(stream_params(), None)
+        .await
+        .unwrap()
+        .events();
+
+    let mut out = Vec::new();
+    while let Some(item) = events.next().await {
+        out.push(item.unwrap());
+    }
+
+    assert_eq!(out.len(), 8);
+    assert!(
+        matches!(&out[0], MessageStreamEvent::Thinking { delta, snapshot } if delta == "Hmm" && snapshot == "Hmm")
+    );
+    assert!(matches!(&out[1], MessageStreamEvent::Signature { signature } if signature == "sig"));
+    assert!(
+        matches!(&out[2], MessageStreamEvent::ContentBlockDone(ContentBlock::Thinking(b)) if b.signature == "sig")
+    );
+    assert!(
+        matches!(&out[3], MessageStreamEvent::Text { delta, snapshot } if delta == "Hello" && snapshot == "Hello")
+    );
+    assert!(
+        matches!(&out[4], MessageStreamEvent::Citation { snapshot, .. } if snapshot.len() == 1)
+    );
+    assert!(
+        matches!(&out[5], MessageStreamEvent::Text { delta, snapshot } if delta == ", world" && snapshot == "Hello, world")
+    );
+    assert!(
+        matches!(&out[6], MessageStreamEvent::ContentBlockDone(ContentBlock::Text(b)) if b.text == "Hello, world")
+    );
+    match &out[7] {
+        MessageStreamEvent::MessageDone(message) => {
+            assert_eq!(message.text(), "Hello, world");
+            assert_eq!(message.stop_reason.as_deref(), Some("end_turn"));
+        }
+        other => panic!("expected MessageDone, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn message_stream_text_stream_yields_only_text() {
+    let server = MockServer::start().await;
+    mount_sse(&server, &thinking_and_text_events()).await;
+
+    let client = client_for(&server);
+    let mut text = client
+        .messages
+        .stream(stream_params(), None)
+        .await
+        .unwrap()
+        .text_stream();
+
+    let mut deltas = Vec::new();
+    while let Some(delta) = text.next().await {
+        deltas.push(delta.unwrap());
+    }
+    assert_eq!(deltas, vec!["Hello", ", world"]);
+}
+
+#[tokio::test]
+async fn with_options_overrides_only_given_fields() {
+    let server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/v1/models"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&server)
+        .await;
+
+    let client = Anthropic::builder()
+        .api_key("test-key")
+        .base_url(server.uri())
+        .timeout(Duration::from_millis(200))
+        .max_retries(0)
+        .default_header(
+            HeaderName::from_static("x-team"),
+            HeaderValue::from_static("search"),
+        )
+        .build()
+        .unwrap();
+
+    let derived = client
+        .with_options(ClientOptions {
+            max_retries: Some(1),
+            ..ClientOptions::empty()
+        })
+        .unwrap();
+
+    let _ = derived.models.list(None, None).await.unwrap_err();
+
+    let reqs = server.received_requests().await.unwrap();
+    assert_eq!(reqs.len(), 2);
+    let h = &reqs[1].headers;
+    assert_eq!(h.get("x-api-key").unwrap().to_str().unwrap(), "test-key");
+    assert_eq!(h.get("x-team").unwrap().to_str().unwrap(), "search");
+    assert_eq!(
+        h.get("x-stainless-retry-count").unwrap().to_str().unwrap(),
+        "1"
+    );
+}
+
+#[tokio::test]
+async fn http_config_routes_through_proxy() {
+    let proxy = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/v1/models"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+          "data": [],
+          "has_more": false,
+          "first_id": null,
+          "last_id": null
+        })))
+        .mount(&proxy)
+        .await;
+
+    let client = Anthropic::builder()
+        .api_key("test-key")
+        .base_url("http://api.anthropic.invalid")
+        .max_retries(0)
+        .http_config(HttpConfig {
+            pool_idle_timeout: Some(Duration::from_secs(30)),
+            tcp_keepalive: Some(Duration::from_secs(60)),
+            ..HttpConfig::default().proxy(reqwest::Proxy::http(proxy.uri()).unwrap())
+        })
+        .build()
+        .unwrap();
+    client.models.list(None, None).await.unwrap();
+
+    let reqs = proxy.received_requests().await.unwrap();
+    assert_eq!(reqs.len(), 1);
+    assert_eq!(reqs[0].url.host_str(), Some("api.anthropic.invalid"));
+
+    let err = Anthropic::builder()
+        .api_key("test-key")
+        .http_client(reqwest::Client::new())
+        .connect_timeout(Duration::from_secs(1))
+        .build()
+        .err()
+        .unwrap();
+    assert!(matches!(err, Error::Internal(_)));
+}
+
+#[derive(Debug, Default)]
+struct FakeTransport {
+    requests: Mutex<Vec<(Method, String, Option<Bytes>)>>,
+}
+
+impl HttpTransport for FakeTransport {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, Error>> {
+        Box::pin(async move {
+            let attempt = {
+                let mut requests = self.requests.lock().unwrap();
+                requests.push((
+                    request.method.clone(),
+                    request.url.path().to_string(),
+                    request.body.as_ref().and_then(|b| b.as_bytes()).cloned(),
+                ));
+                requests.len()
+            };
+            if attempt == 1 {
+                return Err(Error::Connection("connection reset".into()));
+            }
+
+            let mut headers = HeaderMap::new();
+            headers.insert("request-id", HeaderValue::from_static("req_fake"));
+            let body = json!({
+              "id": "msg_fake",
+              "type": "message",
+              "role": "assistant",
+              "content": [{"type": "text", "text": "from memory"}]
+            });
+            Ok(HttpResponse::new(
+                StatusCode::OK,
+                headers,
+                serde_json::to_vec(&body).unwrap(),
+            ))
+        })
+    }
+}
+
+#[tokio::test]
+async fn custom_transport_receives_requests_and_retries_connection_errors() {
+    let transport = Arc::new(FakeTransport::default());
+    let client = Anthropic::builder()
+        .api_key("test-key")
+        .base_url("http://fake.invalid")
+        .max_retries(1)
+        .transport(transport.clone())
+        .build()
+        .unwrap();
+
+    let resp = client
+        .messages
+        .create_with_response(
+            MessageCreateParams {
+                model: "test-model".to_string(),
+                max_tokens: 16,
+                messages: vec![MessageParam::user("hi")],
+                ..Default::default()
+            },
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp.request_id.as_deref(), Some("req_fake"));
+    assert_eq!(resp.data.text(), "from memory");
+
+    let requests = transport.requests.lock().unwrap();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[1].0, Method::POST);
+    assert_eq!(requests[1].1, "/v1/messages");
+    let body: serde_json::Value = serde_json::from_slice(requests[1].2.as_ref().unwrap()).unwrap();
+    assert_eq!(body["model"], "test-model");
+}
+
+#[derive(Debug, Default)]
+struct RecordingMiddleware {
+    statuses: Mutex<Vec<u16>>,
+}
+
+impl Middleware for RecordingMiddleware {
+    fn handle<'a>(
+        &'a self,
+        mut request: HttpRequest,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, Result<HttpResponse, Error>> {
+        Box::pin(async move {
+            request
+                .headers
+                .insert("x-gateway-signature", HeaderValue::from_static("signed"));
+            let response = next.run(request).await?;
+            self.statuses.lock().unwrap().push(response.status.as_u16());
+            Ok(response)
+        })
+    }
+}
+
+#[tokio::test]
+async fn middleware_wraps_requests_and_can_short_circuit() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/v1/models/claude-test"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+          "id": "claude-test",
+          "type": "model",
+          "display_name": "Claude Test",
+          "created_at": "2025-01-01T00:00:00Z"
+        })))
+        .mount(&server)
+        .await;
+
+    let recorder = Arc::new(RecordingMiddleware::default());
+    let client = Anthropic::builder()
+        .api_key("test-key")
+        .base_url(server.uri())
+        .middleware(recorder.clone())
+        .build()
+        .unwrap();
+
+    let model = client
+        .models
+        .retrieve("claude-test", None, None)
+        .await
+        .unwrap();
+    assert_eq!(model.id, "claude-test");
+    assert_eq!(*recorder.statuses.lock().unwrap(), vec![200]);
+    let reqs = server.received_requests().await.unwrap();
+    assert_eq!(
+        reqs[0].headers.get("x-gateway-signature").unwrap(),
+        "signed"
+    );
+
+    let cached = middleware::from_fn(|_request, _next| {
+        Box::pin(async {
+            let body = json!({
+              "id": "cached-model",
+              "type": "model",
+              "display_name": "Cached",
+              "created_at": "2025-01-01T00:00:00Z"
+            });
+            Ok(HttpResponse::new(
+                StatusCode::OK,
+                HeaderMap::new(),
+                serde_json::to_vec(&body).unwrap(),
+            ))
+        })
+    });
+    let model = client
+        .models
+        .retrieve(
+            "claude-test",
+            None,
+            Some(RequestOptions::default().middleware(Arc::new(cached))),
+        )
+        .await
+        .unwrap();
+    assert_eq!(model.id, "cached-model");
+    assert_eq!(*recorder.statuses.lock().unwrap(), vec![200, 200]);
+    assert_eq!(server.received_requests().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn retry_policy_controls_statuses_and_deadline() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/v1/models"))
+        .respond_with(ResponseTemplate::new(503).set_body_json(json!({
+          "type": "error",
+          "error": {"type": "api_error", "message": "unavailable"}
+        })))
+        .mount(&server)
+        .await;
+
+    let client = Anthropic::builder()
+        .api_key("test-key")
+        .base_url(server.uri())
+        .max_retries(3)
+        .retry_policy(RetryPolicy::default().initial_delay(Duration::from_millis(1)))
+        .build()
+        .unwrap();
+    let received = || async { server.received_requests().await.unwrap().len() };
+
+    assert!(client.models.list(None, None).await.is_err());
+    assert_eq!(received().await, 4);
+
+    let no_server_errors = RequestOptions::default().retry_policy(
+        RetryPolicy::default()
+            .initial_delay(Duration::from_millis(1))
+            .retry_server_errors(false),
+    );
+    assert!(client
+        .models
+        .list(None, Some(no_server_errors))
+        .await
+        .is_err());
+    assert_eq!(received().await, 5);
+
+    let deadline = RequestOptions::default().retry_policy(
+        RetryPolicy::default()
+            .initial_delay(Duration::from_millis(80))
+            .multiplier(1.0)
+            .jitter(0.0)
+            .deadline(Duration::from_millis(100)),
+    );
+    assert!(client.models.list(None, Some(deadline)).await.is_err());
+    assert_eq!(received().await, 7);
+}
+
+#[tokio::test]
+async fn never_retry_policy_ignores_x_should_retry() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/v1/models"))
+        .respond_with(
+            ResponseTemplate::new(529)
+                .insert_header("x-should-retry", "true")
+                .insert_header("retry-after-ms", "1")
+                .set_body_json(json!({
+                  "type": "error",
+                  "error": {"type": "overloaded_error", "message": "Overloaded"}
+                })),
+        )
+        .mount(&server)
+        .await;
+
+    let client = Anthropic::builder()
+        .api_key("test-key")
+        .base_url(server.uri())
+        .max_retries(3)
+        .retry_policy(RetryPolicy::never())
+        .build()
+        .unwrap();
+    assert!(client.models.list(None, None).await.is_err());
+    assert_eq!(server.received_requests().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn overloaded_errors_are_typed_for_responses_and_stream_events() {
+    let server = MockServer::start().await;
+    let overloaded = json!({
+      "type": "error",
+      "error": {"type": "overloaded_error", "message": "Overloaded"}
+    });
+    Mock::given(method("GET"))
+        .and(path("/v1/models"))
+        .respond_with(
+            ResponseTemplate::new(529)
+                .insert_header("retry-after-ms", "1")
+                .set_body_json(overloaded.clone()),
+        )
+        .mount(&server)
+        .await;
+    mount_sse(
+        &server,
+        &[
+            json!({
+              "type": "message_start",
+              "message": {"id": "msg_1", "type": "message", "role": "assistant", "content": []}
+            }),
+            overloaded,
+        ],
+    )
+    .await;
+
+    let client = client_for(&server);
+    let err = client.models.list(None, None).await.unwrap_err();
+    match err {
+        Error::Http(HttpApiError::Overloaded(api)) => {
+            assert_eq!(api.status.map(|s| s.as_u16()), Some(529));
+            assert_eq!(api.kind(), Some(ErrorKind::Overloaded));
+            assert_eq!(api.message.as_deref(), Some("Overloaded"));
+        }
+        other => panic!("expected overloaded error, got {other:?}"),
+    }
+    assert_eq!(server.received_requests().await.unwrap().len(), 3);
+
+    let mut stream = client
+        .messages
+        .create_stream(stream_params(), None)
+        .await
+        .unwrap();
+    assert!(stream.next().await.unwrap().is_ok());
+    match stream.next().await.unwrap().unwrap_err() {
+        Error::Http(err) => {
+            assert!(matches!(err, HttpApiError::Overloaded(_)));
+            assert_eq!(err.kind(), Some(ErrorKind::Overloaded));
+            assert_eq!(err.api_error().status, None);
+        }
+        other => panic!("expected overloaded error, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn rate_limiter_holds_requests_until_retry_after() {
+    let server = MockServer::start().await;
+    let calls = Arc::new(AtomicUsize::new(0));
+    Mock::given(method("GET"))
+        .and(path("/v1/models"))
+        .respond_with(ScriptedResponder {
+            calls: calls.clone(),
+            responses: vec![
+                ResponseTemplate::new(429)
+                    .insert_header("retry-after", "1")
+                    .insert_header("anthropic-ratelimit-requests-limit", "50")
+                    .insert_header("anthropic-ratelimit-requests-remaining", "0")
+                    .set_body_json(json!({
+                      "type": "error",
+                      "error": {"type": "rate_limit_error", "message": "slow down"}
+                    })),
+                ResponseTemplate::new(200).set_body_json(json!({
+                  "data": [],
+                  "has_more": false,
+                  "first_id": null,
+                  "last_id": null
+                })),
+            ],
+        })
+        .mount(&server)
+        .await;
+
+    let client = Anthropic::builder()
+        .api_key("test-key")
+        .base_url(server.uri())
+        .max_retries(0)
+        .rate_limiter(Arc::new(RateLimiter::new()))
+        .build()
+        .unwrap();
+
+    let err = client.models.list(None, None).await.unwrap_err();
+    assert!(matches!(err, Error::Http(HttpApiError::RateLimit(_))));
+
+    let started = std::time::Instant::now();
+    client.models.list(None, None).await.unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(900));
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn rate_limiter_estimator_can_call_count_tokens() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/messages/count_tokens"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({"input_tokens": 7})))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/v1/messages"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+          "id": "msg_1", "type": "message", "role": "assistant", "model": "test-model",
+          "stop_reason": "end_turn", "content": [{"type": "text", "text": "hi"}]
+        })))
+        .mount(&server)
+        .await;
+
+    let client_slot = Arc::new(std::sync::OnceLock::<Anthropic>::new());
+    let estimates = Arc::new(AtomicUsize::new(0));
+    let limiter = {
+        let client_slot = client_slot.clone();
+        let estimates = estimates.clone();
+        RateLimiter::new().input_token_estimator(move |request| {
+            let body: MessageCountTokensParams =
+                serde_json::from_slice(request.body.as_ref().unwrap().as_bytes().unwrap()).unwrap();
+            let client = client_slot.get().unwrap().clone();
+            estimates.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async move {
+                client
+                    .messages
+                    .count_tokens(body, None)
+                    .await
+                    .map(|count| count.input_tokens)
+                    .unwrap_or(0)
+            })
+        })
+    };
+    let client = Anthropic::builder()
+        .api_key("test-key")
+        .base_url(server.uri())
+        .rate_limiter(Arc::new(limiter))
+        .build()
+        .unwrap();
+    assert!(client_slot.set(client.clone()).is_ok());
+
+    tokio::time::timeout(
+        Duration::from_secs(5),
+        client.messages.create(stream_params(), None),
+    )
+    .await
+    .expect("the estimator's count_tokens call must not be estimated itself")
+    .unwrap();
+    assert_eq!(estimates.load(Ordering::SeqCst), 1);
+    assert_eq!(server.received_requests().await.unwrap().len(), 2);
+}
+
+fn with_rate_limit_headers(template: ResponseTemplate) -> ResponseTemplate {
+    template
+        .insert_header("anthropic-ratelimit-requests-limit", "50")
+        .insert_header("anthropic-ratelimit-requests-remaining", "49")
+        .insert_header("anthropic-ratelimit-requests-reset", "2025-01-01T00:00:30Z")
+        .insert_header("anthropic-ratelimit-input-tokens-limit", "20000")
+        .insert_header("anthropic-ratelimit-input-tokens-remaining", "19500")
+        .insert_header(
+            "anthropic-ratelimit-input-tokens-reset",
+            "2025-01-01T01:00:30.5+01:00",
+        )
+        .insert_header("anthropic-ratelimit-tokens-remaining", "23000")
+}
+
+#[tokio::test]
+async fn rate_limit_info_is_parsed_from_headers() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/v1/models"))
+        .respond_with(
+            with_rate_limit_headers(ResponseTemplate::new(200)).set_body_json(
+                json!({"data": [], "has_more": false, "first_id": null, "last_id": null}),
+            ),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/v1/models/missing"))
+        .respond_with(
+            with_rate_limit_headers(ResponseTemplate::new(404))
+                .insert_header("retry-after", "3")
+                .set_body_json(json!({
+                  "type": "error",
+                  "error": {"type": "not_found_error", "message": "missing"}
+                })),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/v1/messages"))
+        .respond_with(
+            with_rate_limit_headers(ResponseTemplate::new(200))
+                .insert_header("content-type", "text/event-stream")
+                .set_body_string(sse_body(&thinking_and_text_events())),
+        )
+        .mount(&server)
+        .await;
+
+    let client = client_for(&server);
+    let resp = client.models.list_with_response(None, None).await.unwrap();
+    let info = resp.rate_limit();
+    let reset = std::time::UNIX_EPOCH + Duration::from_secs(1_735_689_630);
+    assert_eq!(info.requests.limit, Some(50));
+    assert_eq!(info.requests.remaining, Some(49));
+    assert_eq!(info.requests.reset, Some(reset));
+    assert_eq!(info.input_tokens.remaining, Some(19_500));
+    assert_eq!(
+        info.input_tokens.reset,
+        Some(reset + Duration::from_millis(500))
+    );
+    assert_eq!(info.output_tokens, Default::default());
+    assert_eq!(info.tokens.remaining, Some(23_000));
+    assert_eq!(info.retry_after, None);
+
+    match client.models.retrieve("missing", None, None).await {
+        Err(Error::Http(err)) => {
+            let info = &err.api_error().rate_limit;
+            assert_eq!(info.requests.remaining, Some(49));
+            assert_eq!(info.retry_after, Some(Duration::from_secs(3)));
+        }
+        other => panic!("expected http error, got {other:?}"),
+    }
+
+    let stream = client.messages.stream(stream_params(), None).await.unwrap();
+    assert_eq!(stream.rate_limit().requests.remaining, Some(49));
+}
+
+#[tokio::test]
+async fn circuit_breaker_opens_after_failures_and_recovers() {
+    let server = MockServer::start().await;
+    let calls = Arc::new(AtomicUsize::new(0));
+    let server_error = ResponseTemplate::new(500).set_body_json(json!({
+      "type": "error",
+      "error": {"type": "api_error", "message": "boom"}
+    }));
+    Mock::given(method("POST"))
+        .and(path("/v1/messages"))
+        .respond_with(ScriptedResponder {
+            calls: calls.clone(),
+            responses: vec![
+                server_error.clone(),
+                server_error,
+                ResponseTemplate::new(200).set_body_json(json!({
+                  "id": "msg_1",
+                  "type": "message",
+                  "role": "assistant",
+                  "content": []
+                })),
+            ],
+        })
+        .mount(&server)
+        .await;
+
+    let breaker = Arc::new(
+        CircuitBreaker::new()
+            .scope(CircuitScope::Model)
+            .minimum_requests(2)
+            .failure_rate(0.5)
+            .open_duration(Duration::from_millis(200)),
+    );
+    let client = Anthropic::builder()
+        .api_key("test-key")
+        .base_url(server.uri())
+        .max_retries(0)
+        .circuit_breaker(breaker.clone())
+        .build()
+        .unwrap();
+
+    for _ in 0..2 {
+        let err = client.messages.create(stream_params(), None).await;
+        assert!(matches!(err, Err(Error::Http(_))));
+    }
+    assert_eq!(breaker.state("test-model"), CircuitState::Open);
+
+    let err = client.messages.create(stream_params(), None).await;
+    assert!(matches!(err, Err(Error::CircuitOpen { key }) if key == "test-model"));
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    tokio::time::sleep(Duration::from_millis(250)).await;
+    assert_eq!(breaker.state("test-model"), CircuitState::HalfOpen);
+    client.messages.create(stream_params(), None).await.unwrap();
+    assert_eq!(breaker.state("test-model"), CircuitState::Closed);
+    assert_eq!(
+        breaker.states().get("test-model"),
+        Some(&CircuitState::Closed)
+    );
+}
+
+#[cfg(feature = "tracing")]
+#[derive(Clone, Default)]
+struct LogBuffer(Arc<Mutex<Vec<u8>>>);
+
+#[cfg(feature = "tracing")]
+impl std::io::Write for LogBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "tracing")]
+#[tokio::test]
+async fn tracing_records_request_spans_retries_and_stream_events() {
+    use tracing_subscriber::util::SubscriberInitExt;
+
+    let logs = LogBuffer::default();
+    let writer = logs.clone();
+    let _guard = tracing_subscriber::fmt()
+        .with_max_level(tracing_subscriber::filter::LevelFilter::TRACE)
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .finish()
+        .set_default();
+
+    let server = MockServer::start().await;
+    let calls = Arc::new(AtomicUsize::new(0));
+    Mock::given(method("POST"))
+        .and(path("/v1/messages"))
+        .respond_with(ScriptedResponder {
+            calls: calls.clone(),
+            responses: vec![
+                ResponseTemplate::new(529).insert_header("retry-after-ms", "1"),
+                ResponseTemplate::new(200)
+                    .insert_header("request-id", "req_traced")
+                    .insert_header("content-type", "text/event-stream")
+                    .set_body_string(sse_body(&thinking_and_text_events())),
+            ],
+        })
+        .mount(&server)
+        .await;
+
+    let client = client_for(&server);
+    let stream = client.messages.stream(stream_params(), None).await.unwrap();
+    stream.into_final_message().await.unwrap();
+
+    let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+    assert!(logs.contains("anthropic.request"));
+    assert!(logs.contains("model=\"test-model\""));
+    assert!(logs.contains("retrying request"));
+    assert!(logs.contains("request_id=\"req_traced\""));
+    assert!(logs.contains("stream event"));
+    assert!(logs.contains("stream finished"));
+    assert!(logs.contains("\"x-api-key\": \"[redacted]\""));
+    assert!(!logs.contains("test-key"));
+}
+
+// Delivers the first SSE event straight away and holds the rest of the body back.
+#[cfg(feature = "otel")]
+#[derive(Debug)]
+struct DelayAfterFirstEvent(Duration);
+
+#[cfg(feature = "otel")]
+impl Middleware for DelayAfterFirstEvent {
+    fn handle<'a>(
+        &'a self,
+        request: HttpRequest,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, Result<HttpResponse, Error>> {
+        Box::pin(async move {
+            let response = next.run(request).await?;
+            let (status, headers) = (response.status, response.headers.clone());
+            let body = response.bytes().await?;
+            let split = body
+                .windows(2)
+                .position(|w| w == b"\n\n")
+                .map_or(body.len(), |i| i + 2);
+            let (head, tail) = (body.slice(..split), body.slice(split..));
+            let delay = self.0;
+            let body = futures_util::stream::once(async move { Ok(head) })
+                .chain(futures_util::stream::once(async move {
+                    tokio::time::sleep(delay).await;
+                    Ok(tail)
+                }))
+                .boxed();
+            Ok(HttpResponse {
+                status,
+                headers,
+                body,
+            })
+        })
+    }
+}
+
+#[cfg(feature = "otel")]
+#[tokio::test]
+async fn otel_records_gen_ai_metrics_and_spans() {
+    use opentelemetry::global;
+    use opentelemetry_sdk::metrics::data::{AggregatedMetrics, MetricData};
+    use opentelemetry_sdk::metrics::{InMemoryMetricExporter, PeriodicReader, SdkMeterProvider};
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+
+    let metric_exporter = InMemoryMetricExporter::default();
+    let meter_provider = SdkMeterProvider::builder()
+        .with_reader(PeriodicReader::builder(metric_exporter.clone()).build())
+        .build();
+    global::set_meter_provider(meter_provider.clone());
+    let span_exporter = InMemorySpanExporter::default();
+    let tracer_provider = SdkTracerProvider::builder()
+        .with_simple_exporter(span_exporter.clone())
+        .build();
+    global::set_tracer_provider(tracer_provider.clone());
+
+    let server = MockServer::start().await;
+    let mut events = thinking_and_text_events();
+    events[0]["message"]["model"] = json!("otel-model-20250101");
+    events[0]["message"]["usage"] = json!({"input_tokens": 12, "output_tokens": 1});
+    mount_sse(&server, &events).await;
+
+    let client = Anthropic::builder()
+        .api_key("test-key")
+        .base_url(server.uri())
+        .middleware(Arc::new(DelayAfterFirstEvent(Duration::from_millis(150))))
+        .build()
+        .unwrap();
+    let params = MessageCreateParams {
+        model: "otel-model".to_string(),
+        ..stream_params()
+    };
+    let stream = client.messages.stream(params, None).await.unwrap();
+    stream.into_final_message().await.unwrap();
+
+    meter_provider.force_flush().unwrap();
+    let has = |attrs: &[&opentelemetry::KeyValue], key: &str, value: &str| {
+        attrs
+            .iter()
+            .any(|kv| kv.key.as_str() == key && kv.value.as_str() == value)
+    };
+    let mut seen = std::collections::BTreeMap::new();
+    let mut sums = std::collections::BTreeMap::new();
+    for resource in metric_exporter.get_finished_metrics().unwrap() {
+        for scope in resource.scope_metrics() {
+            for metric in scope.metrics() {
+                match metric.data() {
+                    AggregatedMetrics::F64(MetricData::Histogram(h)) => {
+                        for point in h.data_points() {
+                            let attrs: Vec<_> = point.attributes().collect();
+                            if has(&attrs, "gen_ai.request.model", "otel-model") {
+                                seen.insert(metric.name().to_string(), point.count());
+                                sums.insert(metric.name().to_string(), point.sum());
+                            }
+                        }
+                    }
+                    AggregatedMetrics::U64(MetricData::Histogram(h)) => {
+                        for point in h.data_points() {
+                            let attrs: Vec<_> = point.attributes().collect();
+                            if has(&attrs, "gen_ai.request.model", "otel-model")
+                                && has(&attrs, "gen_ai.token.type", "input")
+                            {
+                                seen.insert(metric.name().to_string(), point.sum());
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    assert_eq!(seen.get("gen_ai.client.operation.duration"), Some(&1));
+    assert_eq!(
+        seen.get("gen_ai.client.operation.time_to_first_chunk"),
+        Some(&1)
+    );
+    // The first delta only arrives after the delay that follows `message_start`.
+    assert!(sums["gen_ai.client.operation.time_to_first_chunk"] >= 0.15);
+    assert_eq!(seen.get("gen_ai.client.token.usage"), Some(&12));
+
+    let spans = span_exporter.get_finished_spans().unwrap();
+    let span = spans
+        .iter()
+        .find(|s| s.name == "chat otel-model")
+        .expect("chat span");
+    let attr = |key: &str| {
+        span.attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == key)
+            .map(|kv| kv.value.to_string())
+    };
+    assert_eq!(attr("gen_ai.request.model").as_deref(), Some("otel-model"));
+    assert_eq!(
+        attr("gen_ai.response.model").as_deref(),
+        Some("otel-model-20250101")
+    );
+    assert_eq!(
+        attr("gen_ai.response.finish_reasons").as_deref(),
+        Some("[\"end_turn\"]")
+    );
+    assert_eq!(attr("gen_ai.usage.output_tokens").as_deref(), Some("4"));
+}
+
+#[tokio::test]
+async fn idempotency_keys_are_stable_across_retries() {
+    let server = MockServer::start().await;
+    let message = json!({
+      "id": "msg_1", "type": "message", "role": "assistant", "model": "test-model",
+      "stop_reason": "end_turn", "content": [{"type": "text", "text": "ok"}]
+    });
+    Mock::given(method("POST"))
+        .and(path("/v1/messages"))
+        .respond_with(ScriptedResponder {
+            calls: Arc::new(AtomicUsize::new(0)),
+            responses: vec![
+                ResponseTemplate::new(500),
+                ResponseTemplate::new(200).set_body_json(message),
+            ],
+        })
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/v1/messages/batches"))
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+          "type": "error",
+          "error": {"type": "invalid_request_error", "message": "bad batch"}
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/v1/models"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(
+            json!({"data": [], "has_more": false, "first_id": null, "last_id": null}),
+        ))
+        .mount(&server)
+        .await;
+
+    let client = Anthropic::builder()
+        .api_key("test-key")
+        .base_url(server.uri())
+        .retry_policy(RetryPolicy::default().initial_delay(Duration::from_millis(1)))
+        .build()
+        .unwrap();
+    let params = MessageCreateParams {
+        model: "test-model".to_string(),
+        max_tokens: 16,
+        messages: vec![MessageParam::user("hi")],
+        ..Default::default()
+    };
+    let first = client
+        .messages
+        .create_with_response(params.clone(), None)
+        .await
+        .unwrap();
+    let second = client
+        .messages
+        .create_with_response(params, None)
+        .await
+        .unwrap();
+
+    let err = client
+        .messages
+        .batches
+        .create(
+            BatchCreateParams { requests: vec![] },
+            Some(RequestOptions::default().idempotency_key("batch-1")),
+        )
+        .await
+        .unwrap_err();
+    match err {
+        Error::Http(HttpApiError::BadRequest(api)) => {
+            assert_eq!(api.idempotency_key.as_deref(), Some("batch-1"));
+        }
+        other => panic!("expected BadRequest, got {other:?}"),
+    }
+    client.models.list(None, None).await.unwrap();
+
+    let reqs = server.received_requests().await.unwrap();
+    let keys: Vec<Option<&str>> = reqs
+        .iter()
+        .map(|r| {
+            r.headers
+                .get("idempotency-key")
+                .map(|v| v.to_str().unwrap())
+        })
+        .collect();
+    assert_eq!(keys.len(), 5);
+    let generated = first.idempotency_key.as_deref().unwrap();
+    assert!(generated.starts_with("anthropic-sdk-rust-retry-"));
+    assert_eq!(keys[0], Some(generated));
+    assert_eq!(keys[1], Some(generated));
+    assert_eq!(keys[2], second.idempotency_key.as_deref());
+    assert_ne!(keys[2], keys[1]);
+    assert_eq!(keys[3], Some("batch-1"));
+    assert_eq!(keys[4], None);
+}
+
+#[cfg(feature = "bedrock")]
+fn event_stream_frame(headers: &[(&str, &str)], payload: &[u8]) -> Vec<u8> {
+    let mut encoded_headers = Vec::new();
+    for (name, value) in headers {
+        encoded_headers.push(name.len() as u8);
+        encoded_headers.extend_from_slice(name.as_bytes());
+        encoded_headers.push(7);
+        encoded_headers.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        encoded_headers.extend_from_slice(value.as_bytes());
+    }
+    let total_len = 12 + encoded_headers.len() + payload.len() + 4;
+    let mut frame = Vec::with_capacity(total_len);
+    frame.extend_from_slice(&(total_len as u32).to_be_bytes());
+    frame.extend_from_slice(&(encoded_headers.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&crc32fast::hash(&frame).to_be_bytes());
+    frame.extend_from_slice(&encoded_headers);
+    frame.extend_from_slice(payload);
+    frame.extend_from_slice(&crc32fast::hash(&frame).to_be_bytes());
+    frame
+}
+
+#[cfg(feature = "bedrock")]
+#[tokio::test]
+async fn bedrock_signs_requests_and_decodes_event_streams() {
+    use anthropic_sdk::bedrock::{AnthropicBedrock, AwsCredentials};
+    use base64::Engine as _;
+
+    let server = MockServer::start().await;
+    let message = json!({
+      "id": "msg_1", "type": "message", "role": "assistant", "model": "claude-test",
+      "stop_reason": "end_turn", "content": [{"type": "text", "text": "from bedrock"}]
+    });
+    Mock::given(method("POST"))
+        .and(path("/model/anthropic.claude-test-v1%3A0/invoke"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("x-amzn-requestid", "bedrock-req-1")
+                .set_body_json(message),
+        )
+        .mount(&server)
+        .await;
+
+    let mut frames = Vec::new();
+    for event in thinking_and_text_events() {
+        let chunk = json!({
+            "bytes": base64::engine::general_purpose::STANDARD.encode(event.to_string())
+        });
+        frames.extend(event_stream_frame(
+            &[
+                (":event-type", "chunk"),
+                (":content-type", "application/json"),
+                (":message-type", "event"),
+            ],
+            chunk.to_string().as_bytes(),
+        ));
+    }
+    Mock::given(method("POST"))
+        .and(path(
+            "/model/anthropic.claude-test-v1%3A0/invoke-with-response-stream",
+        ))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "application/vnd.amazon.eventstream")
+                .set_body_bytes(frames),
+        )
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/model/anthropic.claude-busy/invoke"))
+        .respond_with(
+            ResponseTemplate::new(429)
+                .insert_header(
+                    "x-amzn-errortype",
+                    "ThrottlingException:http://internal.amazon.com/",
+                )
+                .set_body_json(json!({"message": "Too many requests"})),
+        )
+        .mount(&server)
+        .await;
+
+    let client = AnthropicBedrock::builder()
+        .region("us-west-2")
+        .credentials(AwsCredentials::new("AKIDEXAMPLE", "secret").session_token("session"))
+        .base_url(server.uri())
+        .max_retries(0)
+        .build()
+        .unwrap();
+    let params = MessageCreateParams {
+        model: "anthropic.claude-test-v1:0".to_string(),
+        ..stream_params()
+    };
+
+    let resp = client
+        .messages
+        .create_with_response(params.clone(), None)
+        .await
+        .unwrap();
+    assert_eq!(resp.data.text(), "from bedrock");
+    assert_eq!(resp.request_id.as_deref(), Some("bedrock-req-1"));
+
+    let message = client
+        .messages
+        .stream(params, None)
+        .await
+        .unwrap()
+        .into_final_message()
+        .await
+        .unwrap();
+    assert_eq!(message.text(), "Hello, world");
+    assert_eq!(message.usage.output_tokens, 4);
+
+    let err = client
+        .messages
+        .create(
+            MessageCreateParams {
+                model: "anthropic.claude-busy".to_string(),
+                ..stream_params()
+            },
+            None,
+        )
+        .await
+        .unwrap_err();
+    match err {
+        Error::Http(HttpApiError::RateLimit(api)) => {
+            assert_eq!(api.kind(), Some(ErrorKind::RateLimit));
+            assert_eq!(api.message.as_deref(), Some("Too many requests"));
+        }
+        other => panic!("expected RateLimit, got {other:?}"),
+    }
+    assert!(matches!(
+        client.messages.count_tokens(Default::default(), None).await,
+        Err(Error::Internal(_))
+    ));
+
+    let reqs = server.received_requests().await.unwrap();
+    assert_eq!(reqs.len(), 3);
+    let body: serde_json::Value = serde_json::from_slice(&reqs[0].body).unwrap();
+    assert_eq!(body["anthropic_version"], "bedrock-2023-05-31");
+    assert!(body.get("model").is_none());
+    let stream_body: serde_json::Value = serde_json::from_slice(&reqs[1].body).unwrap();
+    assert!(stream_body.get("stream").is_none());
+
+    let auth = reqs[0]
+        .headers
+        .get("authorization")
+        .unwrap()
+        .to_str()
+        .unwrap();
+    assert!(auth.starts_with("AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/"));
+    assert!(auth.contains("/us-west-2/bedrock/aws4_request, SignedHeaders=content-type;host;x-amz-date;x-amz-security-token, Signature="));
+    assert_eq!(
+        reqs[0].headers.get("x-amz-security-token").unwrap(),
+        "session"
+    );
+    assert!(reqs[0].headers.get("x-api-key").is_none());
+}
+
+#[cfg(feature = "vertex")]
+#[tokio::test]
+async fn vertex_routes_models_into_the_url_and_caches_tokens() {
+    use anthropic_sdk::vertex::{AnthropicVertex, MetadataServer};
+    use anthropic_sdk::ReqwestTransport;
+    use wiremock::matchers::header;
+
+    #[derive(Debug)]
+    struct PathRecorder {
+        inner: ReqwestTransport,
+        paths: Mutex<Vec<String>>,
+    }
+
+    impl HttpTransport for PathRecorder {
+        fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, Error>> {
+            self.paths
+                .lock()
+                .unwrap()
+                .push(request.url.path().to_string());
+            self.inner.send(request)
+        }
+    }
+
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(
+            "/computeMetadata/v1/instance/service-accounts/default/token",
+        ))
+        .and(header("metadata-flavor", "Google"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({"access_token": "ya29.test", "expires_in": 3600})),
+        )
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let models = "/v1/projects/my-project/locations/us-east5/publishers/anthropic/models";
+    // A revoked token is dropped and fetched again before the request is retried.
+    Mock::given(method("POST"))
+        .and(path(format!("{models}/claude-test@20250101:rawPredict")))
+        .respond_with(ResponseTemplate::new(401))
+        .with_priority(1)
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path(format!("{models}/claude-test@20250101:rawPredict")))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+          "id": "msg_1", "type": "message", "role": "assistant", "model": "claude-test",
+          "stop_reason": "end_turn", "content": [{"type": "text", "text": "from vertex"}]
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path(format!(
+            "{models}/claude-test@20250101:streamRawPredict"
+        )))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("content-type", "text/event-stream")
+                .set_body_string(sse_body(&thinking_and_text_events())),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path(format!("{models}/count-tokens:rawPredict")))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({"input_tokens": 7})))
+        .mount(&server)
+        .await;
+
+    let transport = Arc::new(PathRecorder {
+        inner: ReqwestTransport::new(reqwest::Client::new()),
+        paths: Mutex::new(Vec::new()),
+    });
+    let client = AnthropicVertex::builder()
+        .region("us-east5")
+        .project_id("my-project")
+        .token_provider(Arc::new(MetadataServer::new().base_url(server.uri())))
+        .base_url(server.uri())
+        .transport(transport.clone())
+        .build()
+        .unwrap();
+    let params = MessageCreateParams {
+        model: "claude-test@20250101".to_string(),
+        ..stream_params()
+    };
+
+    let message = client.messages.create(params.clone(), None).await.unwrap();
+    assert_eq!(message.text(), "from vertex");
+    let streamed = client
+        .messages
+        .stream(params, None)
+        .await
+        .unwrap()
+        .into_final_message()
+        .await
+        .unwrap();
+    assert_eq!(streamed.text(), "Hello, world");
+    let count = client
+        .messages
+        .count_tokens(
+            MessageCountTokensParams {
+                model: "claude-test@20250101".to_string(),
+                messages: vec![MessageParam::user("hi")],
+                ..Default::default()
+            },
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(count.input_tokens, 7);
+
+    // Token requests share the configured transport with API requests.
+    let reqs = server.received_requests().await.unwrap();
+    assert_eq!(
+        *transport.paths.lock().unwrap(),
+        reqs.iter()
+            .map(|r| r.url.path().to_string())
+            .collect::<Vec<_>>()
+    );
+    let api_reqs: Vec<_> = reqs
+        .iter()
+        .filter(|r| r.url.path().starts_with("/v1/"))
+        .skip(1)
+        .collect();
+    assert_eq!(api_reqs.len(), 3);
+    for req in &api_reqs {
+        assert_eq!(
+            req.headers.get("authorization").unwrap(),
+            "Bearer ya29.test"
+        );
+        assert!(req.headers.get("x-api-key").is_none());
+        let body: serde_json::Value = serde_json::from_slice(&req.body).unwrap();
+        assert_eq!(body["anthropic_version"], "vertex-2023-10-16");
+    }
+    let create: serde_json::Value = serde_json::from_slice(&api_reqs[0].body).unwrap();
+    assert!(create.get("model").is_none());
+    let stream: serde_json::Value = serde_json::from_slice(&api_reqs[1].body).unwrap();
+    assert_eq!(stream["stream"], true);
+    let count: serde_json::Value = serde_json::from_slice(&api_reqs[2].body).unwrap();
+    assert_eq!(count["model"], "claude-test@20250101");
+}
+
+#[tokio::test]
+async fn credential_providers_are_consulted_per_attempt_and_refreshed_on_401() {
+    use std::time::SystemTime;
+    use wiremock::matchers::header;
+
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/v1/models"))
+        .and(header("authorization", "Bearer token-1"))
+        .respond_with(ResponseTemplate::new(401).set_body_json(json!({
+          "type": "error",
+          "error": {"type": "authentication_error", "message": "token expired"}
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/v1/models"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(
+            json!({"data": [], "has_more": false, "first_id": null, "last_id": null}),
+        ))
+        .mount(&server)
+        .await;
+
+    let fetches = Arc::new(AtomicUsize::new(0));
+    let provider = {
+        let fetches = fetches.clone();
+        RefreshingCredentials::new(move || {
+            let n = fetches.fetch_add(1, Ordering::SeqCst) + 1;
+            Box::pin(async move {
+                Ok(Credentials::auth_token(format!("token-{n}"))
+                    .expires_at(SystemTime::now() + Duration::from_secs(3600)))
+            })
+        })
+    };
+    let client = Anthropic::builder()
+        .base_url(server.uri())
+        .max_retries(0)
+        .credential_provider(Arc::new(provider))
+        .build()
+        .unwrap();
+    client.models.list(None, None).await.unwrap();
+    client.models.list(None, None).await.unwrap();
+    assert_eq!(fetches.load(Ordering::SeqCst), 2);
+
+    let key_file = write_temp_file("sdk-api-key", b"file-key-1\n");
+    let client = Anthropic::builder()
+        .base_url(server.uri())
+        .credential_provider(Arc::new(FileCredentials::api_key(&key_file)))
+        .build()
+        .unwrap();
+    client.models.list(None, None).await.unwrap();
+    std::fs::write(&key_file, "file-key-2").unwrap();
+    std::fs::File::options()
+        .write(true)
+        .open(&key_file)
+        .unwrap()
+        .set_modified(SystemTime::now() + Duration::from_secs(5))
+        .unwrap();
+    client.models.list(None, None).await.unwrap();
+    // A secret that was mounted but not populated yet is an error, not an empty key.
+    std::fs::write(&key_file, " \n").unwrap();
+    std::fs::File::options()
+        .write(true)
+        .open(&key_file)
+        .unwrap()
+        .set_modified(SystemTime::now() + Duration::from_secs(10))
+        .unwrap();
+    assert!(matches!(
+        client.models.list(None, None).await,
+        Err(Error::Credentials(_))
+    ));
+    std::fs::remove_file(&key_file).unwrap();
+
+    let reqs = server.received_requests().await.unwrap();
+    let auth: Vec<_> = reqs
+        .iter()
+        .map(|r| {
+            let get = |name: &str| r.headers.get(name).map(|v| v.to_str().unwrap().to_string());
+            get("authorization").or_else(|| get("x-api-key")).unwrap()
+        })
+        .collect();
+    assert_eq!(
+        auth,
+        [
+            "Bearer token-1",
+            "Bearer token-2",
+            "Bearer token-2",
+            "file-key-1",
+            "file-key-2"
+        ]
+    );
+}